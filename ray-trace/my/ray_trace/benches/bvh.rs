@@ -0,0 +1,81 @@
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use ray_trace::common::color::Color;
+use ray_trace::common::ray::Ray;
+use ray_trace::common::vec3::{Point3, Vec3};
+use ray_trace::geometry::bvh::{BoundingVolumeHierachies, BvhBuildStrategy};
+use ray_trace::geometry::sphere::Sphere;
+use ray_trace::geometry::Geometry;
+use ray_trace::material::lambertian::Lambertian;
+use std::hint::black_box;
+
+const SPHERES: usize = 20_000;
+const RAYS: usize = 10_000;
+
+// small spheres scattered in front of the camera, the same every run
+fn scene(rng: &mut StdRng) -> Vec<Box<dyn Geometry>> {
+    (0..SPHERES)
+        .map(|_| -> Box<dyn Geometry> {
+            Box::new(Sphere::new(
+                Point3::new(
+                    rng.gen_range(-50.0, 50.0),
+                    rng.gen_range(-50.0, 50.0),
+                    rng.gen_range(-100.0, -5.0),
+                ),
+                rng.gen_range(0.05, 0.5),
+                Lambertian::new(Color::newf(0.5, 0.5, 0.5)),
+            ))
+        })
+        .collect()
+}
+
+fn rays(rng: &mut StdRng) -> Vec<Ray> {
+    (0..RAYS)
+        .map(|_| {
+            let direction = Vec3::new(rng.gen_range(-1.0, 1.0), rng.gen_range(-1.0, 1.0), -1.0);
+            Ray::new(Point3::default(), direction)
+        })
+        .collect()
+}
+
+const STRATEGIES: [BvhBuildStrategy; 3] = [
+    BvhBuildStrategy::Midpoint,
+    BvhBuildStrategy::Median,
+    BvhBuildStrategy::SurfaceAreaHeuristic,
+];
+
+fn build(c: &mut Criterion) {
+    let mut group = c.benchmark_group("bvh build");
+    group.sample_size(20);
+    for strategy in STRATEGIES {
+        group.bench_function(format!("{:?}", strategy), |b| {
+            b.iter_batched(
+                || scene(&mut StdRng::seed_from_u64(3)),
+                |objects| BoundingVolumeHierachies::new(objects, 0.0..0.0, strategy),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn hit(c: &mut Criterion) {
+    let mut group = c.benchmark_group("bvh hit");
+    for strategy in STRATEGIES {
+        let mut rng = StdRng::seed_from_u64(3);
+        let bvh = BoundingVolumeHierachies::new(scene(&mut rng), 0.0..0.0, strategy);
+        let rays = rays(&mut rng);
+        group.bench_function(format!("{:?}", strategy), |b| {
+            b.iter(|| {
+                rays.iter()
+                    .filter(|ray| black_box(&bvh).hit(ray, 0.001..f64::INFINITY).is_some())
+                    .count()
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, build, hit);
+criterion_main!(benches);
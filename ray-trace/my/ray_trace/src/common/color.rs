@@ -2,9 +2,7 @@ use crate::common::utils::clamp;
 use crate::common::vec3::{Point3, Vec3};
 use crate::texture::Texture;
 use std::borrow::Cow;
-use std::iter::Sum;
 use std::ops::{Add, Mul};
-use std::process::Output;
 
 /////////////// Rgb Float /////////
 #[derive(Debug, Clone, Default)]
@@ -39,11 +37,11 @@ impl RgbInt {
 /////////// RgbFloat <-> RbgInt ///////
 impl From<&RgbFloat> for RgbInt {
     fn from(c: &RgbFloat) -> Self {
-        // TODO  #[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
+        // arithmetic on colors never clamps, this is where they get cut to 0~1
         Self::new(
-            (c.r * 255.0) as u8,
-            (c.g * 255.0) as u8,
-            (c.b * 255.0) as u8,
+            (clamp(c.r, 0.0..=1.0) * 255.0).round() as u8,
+            (clamp(c.g, 0.0..=1.0) * 255.0).round() as u8,
+            (clamp(c.b, 0.0..=1.0) * 255.0).round() as u8,
         )
     }
 }
@@ -91,7 +89,7 @@ impl Color {
     }
 
     pub fn gradient(&self, ratial: f64, rhs: Self) -> Self {
-        let r = ratial.max(0.0).min(1.0);
+        let r = ratial.clamp(0.0, 1.0);
         self * (1.0 - r) + r * rhs
     }
 }
@@ -102,11 +100,7 @@ impl Add<&Color> for &Color {
     fn add(self, rhs: &Color) -> Self::Output {
         let f_lhs = self.float_form();
         let f_rhs = rhs.float_form();
-        Color::newf(
-            clamp((f_lhs.r + f_rhs.r), 0.0..=1.0),
-            clamp((f_lhs.g + f_rhs.g), 0.0..=1.0),
-            clamp((f_lhs.b + f_rhs.b), 0.0..=1.0),
-        )
+        Color::newf(f_lhs.r + f_rhs.r, f_lhs.g + f_rhs.g, f_lhs.b + f_rhs.b)
     }
 }
 
@@ -136,11 +130,7 @@ impl Mul<f64> for &Color {
     type Output = Color;
     fn mul(self, rhs: f64) -> Self::Output {
         let f = self.float_form();
-        Color::newf(
-            clamp(f.r * rhs, 0.0..=1.0),
-            clamp(f.g * rhs, 0.0..=1.0),
-            clamp(f.b * rhs, 0.0..=1.0),
-        )
+        Color::newf(f.r * rhs, f.g * rhs, f.b * rhs)
     }
 }
 
@@ -165,16 +155,47 @@ impl Mul<Color> for f64 {
     }
 }
 
+// component-wise, a surface color filtering the incoming light
+impl Mul<&Color> for &Color {
+    type Output = Color;
+    fn mul(self, rhs: &Color) -> Self::Output {
+        let f_lhs = self.float_form();
+        let f_rhs = rhs.float_form();
+        Color::newf(f_lhs.r * f_rhs.r, f_lhs.g * f_rhs.g, f_lhs.b * f_rhs.b)
+    }
+}
+
+impl Mul<Color> for &Color {
+    type Output = Color;
+    fn mul(self, rhs: Color) -> Self::Output {
+        self * &rhs
+    }
+}
+
+impl Mul<&Color> for Color {
+    type Output = Color;
+    fn mul(self, rhs: &Color) -> Self::Output {
+        &self * rhs
+    }
+}
+
+impl Mul<Color> for Color {
+    type Output = Color;
+    fn mul(self, rhs: Color) -> Self::Output {
+        &self * &rhs
+    }
+}
+
 impl Texture for Color {
     fn color(&self, _u: f64, _v: f64, _point: &Point3) -> Color {
         self.clone()
     }
 }
 
-impl Into<Vec3> for Color {
-    fn into(self) -> Vec3 {
-        let int_form = self.float_form();
-        Vec3::new(int_form.r as f64, int_form.g as f64, int_form.b as f64)
+impl From<Color> for Vec3 {
+    fn from(c: Color) -> Self {
+        let float_form = c.float_form();
+        Vec3::new(float_form.r, float_form.g, float_form.b)
     }
 }
 
@@ -183,7 +204,7 @@ impl Into<Vec3> for Color {
 fn test_mul() {
     {
         let c = Color::newf(1.0, 1.0, 1.0);
-        assert_eq!((c * 0.5).int_form().r, 127);
+        assert_eq!((c * 0.5).int_form().r, 128);
     }
     {
         let c = Color::newf(1.0, 1.0, 1.0);
@@ -195,15 +216,33 @@ fn test_mul() {
     }
     {
         let c = Color::newf(1.0, 1.0, 1.0);
-        assert_eq!((0.5 * c).int_form().r, 127);
+        assert_eq!((0.5 * c).int_form().r, 128);
     }
 }
 
+#[test]
+fn test_mul_color() {
+    let c_1 = Color::newf(1.0, 0.5, 0.0);
+    let c_2 = Color::new(255, 255, 255);
+    let c = (&c_1 * &c_2).int_form().into_owned();
+    assert_eq!((c.r, c.g, c.b), (255, 128, 0));
+    let c = (Color::newf(0.5, 0.5, 0.5) * c_1).int_form().into_owned();
+    assert_eq!((c.r, c.g, c.b), (128, 64, 0));
+}
+
+#[test]
+fn test_no_clamp_until_int_form() {
+    let c = Color::newf(0.8, 0.8, 0.8) * 2.0 + Color::newf(0.1, 0.1, 0.1);
+    assert!((c.float_form().r - 1.7).abs() < 1e-12);
+    assert_eq!(c.int_form().r, 255);
+    assert_eq!((c * 0.5).int_form().r, 217);
+}
+
 #[test]
 fn test_add() {
     let c_1 = Color::newf(0.0, 0.0, 0.0);
     let c_2 = Color::newf(1.0, 1.0, 1.0);
-    assert_eq!((&c_1 + &c_2).int_form().r, 127);
-    assert_eq!((&c_1 + &c_2).int_form().g, 127);
-    assert_eq!((&c_1 + &c_2).int_form().b, 127);
+    assert_eq!((&c_1 + &c_2).int_form().r, 255);
+    assert_eq!((&c_1 + &c_2).int_form().g, 255);
+    assert_eq!((&c_1 + &c_2).int_form().b, 255);
 }
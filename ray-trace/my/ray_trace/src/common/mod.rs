@@ -1,4 +1,5 @@
-pub(crate) mod color;
-pub(crate) mod ray;
+pub mod color;
+pub mod random;
+pub mod ray;
 mod utils;
-pub(crate) mod vec3;
+pub mod vec3;
@@ -0,0 +1,35 @@
+use rand::rngs::StdRng;
+use rand::{thread_rng, Rng, SeedableRng};
+use std::cell::RefCell;
+
+// the random numbers drawn while tracing. Every thread has its own generator,
+// the painter reseeds it before each sample, so a sample gets the same
+// numbers whichever thread traces it
+thread_local! {
+    static RNG: RefCell<StdRng> =
+        RefCell::new(StdRng::from_rng(thread_rng()).expect("seed from thread_rng"));
+}
+
+pub fn seed(seed: u64) {
+    RNG.with(|rng| *rng.borrow_mut() = StdRng::seed_from_u64(seed));
+}
+
+// uniform in `low..high`
+pub fn gen_range(low: f64, high: f64) -> f64 {
+    RNG.with(|rng| rng.borrow_mut().gen_range(low, high))
+}
+
+////////// UT //////////
+#[test]
+fn test_seed_repeats() {
+    seed(42);
+    let first: Vec<_> = (0..4).map(|_| gen_range(0.0, 1.0)).collect();
+    seed(42);
+    let second: Vec<_> = (0..4).map(|_| gen_range(0.0, 1.0)).collect();
+    assert_eq!(first, second);
+    assert!(first.iter().all(|v| (0.0..1.0).contains(v)));
+
+    // other threads are not affected
+    let other = std::thread::spawn(|| gen_range(0.0, 1.0)).join().unwrap();
+    assert_ne!(other, first[0]);
+}
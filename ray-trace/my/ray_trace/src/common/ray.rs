@@ -4,11 +4,21 @@ use super::vec3::{Point3, Vec3};
 pub struct Ray {
     pub origin: Point3,
     pub direction: Vec3,
+    pub time: f64, // when the ray was shot, for moving objects
 }
 
 impl Ray {
     pub const fn new(origin: Point3, direction: Vec3) -> Self {
-        Self { origin, direction }
+        Self {
+            origin,
+            direction,
+            time: 0.0,
+        }
+    }
+
+    pub const fn time(mut self, time: f64) -> Self {
+        self.time = time;
+        self
     }
 
     pub fn at(&self, unit: f64) -> Point3 {
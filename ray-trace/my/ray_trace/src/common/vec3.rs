@@ -1,6 +1,5 @@
-use crate::common::{color::Color, utils::clamp};
+use crate::common::random;
 
-use rand::{thread_rng, Rng};
 use std::f64::consts::PI;
 use std::iter::Sum;
 use std::ops::{
@@ -15,6 +14,10 @@ pub struct Vec3 {
 }
 
 pub type Point3 = Vec3;
+// linear light along a ray, not clamped, may go above 1.0 for bright emitters.
+// An alias on purpose, like `Point3`: shading needs all of Vec3's arithmetic,
+// and `Color` is the separate type that marks where clamping happens
+pub type Radiance = Vec3;
 
 impl Vec3 {
     pub const fn new(x: f64, y: f64, z: f64) -> Self {
@@ -39,9 +42,9 @@ impl Vec3 {
 
     pub fn random_in_unit_box() -> Self {
         Self::new(
-            thread_rng().gen_range(-1.0, 1.0),
-            thread_rng().gen_range(-1.0, 1.0),
-            thread_rng().gen_range(-1.0, 1.0),
+            random::gen_range(-1.0, 1.0),
+            random::gen_range(-1.0, 1.0),
+            random::gen_range(-1.0, 1.0),
         )
     }
 
@@ -57,8 +60,8 @@ impl Vec3 {
     // hongfendong must_use?
     #[must_use]
     pub fn random_unit() -> Self {
-        let a: f64 = thread_rng().gen_range(0.0, 2 as f64 * PI);
-        let z: f64 = thread_rng().gen_range(-1.0, 1.0);
+        let a: f64 = random::gen_range(0.0, 2.0 * PI);
+        let z: f64 = random::gen_range(-1.0, 1.0);
         let r = (1.0 - z * z).sqrt();
         Self::new(r * a.cos(), r * a.sin(), z)
     }
@@ -100,21 +103,18 @@ impl Vec3 {
         self / self.length()
     }
 
-    // colorize a unit-length-vector
-    pub fn into_color(mut self, samples: usize, gamma: bool) -> Color {
-        if 1 != samples {
-            self /= samples as f64;
-        }
-        if gamma {
-            self.x = self.x.sqrt();
-            self.y = self.y.sqrt();
-            self.z = self.z.sqrt();
-        }
-        Color::newf(
-            clamp(self.x, 0.0..=1.0),
-            clamp(self.y, 0.0..=1.0),
-            clamp(self.z, 0.0..=1.0),
-        )
+    // mirror `self` about the surface with normal `normal`
+    pub fn reflect(&self, normal: &Self) -> Self {
+        self - 2.0 * self.dot(normal) * normal
+    }
+
+    // bend the unit vector `self` through the surface with normal `normal`,
+    // `ratio` is the refractive index of the incident side over the other side
+    pub fn refract(&self, normal: &Self, ratio: f64) -> Self {
+        let cos_theta = (-self).dot(normal).min(1.0);
+        let perpendicular = ratio * (self + cos_theta * normal);
+        let parallel = -(1.0 - perpendicular.length_squared()).abs().sqrt() * normal;
+        perpendicular + parallel
     }
 
     // 反向
@@ -286,6 +286,35 @@ impl Mul<Vec3> for f64 {
     }
 }
 
+// component-wise, used to filter radiance by a surface color
+impl Mul<&Vec3> for &Vec3 {
+    type Output = Vec3;
+    fn mul(self, rhs: &Vec3) -> Self::Output {
+        Vec3::new(self.x * rhs.x, self.y * rhs.y, self.z * rhs.z)
+    }
+}
+
+impl Mul<Vec3> for &Vec3 {
+    type Output = Vec3;
+    fn mul(self, rhs: Vec3) -> Self::Output {
+        self * &rhs
+    }
+}
+
+impl Mul<&Vec3> for Vec3 {
+    type Output = Vec3;
+    fn mul(self, rhs: &Vec3) -> Self::Output {
+        &self * rhs
+    }
+}
+
+impl Mul<Vec3> for Vec3 {
+    type Output = Vec3;
+    fn mul(self, rhs: Vec3) -> Self::Output {
+        &self * &rhs
+    }
+}
+
 impl MulAssign<f64> for Vec3 {
     fn mul_assign(&mut self, rhs: f64) {
         self.x *= rhs;
@@ -435,6 +464,14 @@ fn test_mul_between_f64_and_vec3() {
     }
 }
 
+#[test]
+fn test_mul_between_vec3() {
+    let vec3_1 = Vec3::new(3.0, 5.0, 7.0);
+    let vec3_2 = Vec3::new(11.0, 13.0, 17.0);
+    assert_eq!(&vec3_1 * &vec3_2, Vec3::new(33.0, 65.0, 119.0));
+    assert_eq!(vec3_1 * vec3_2, Vec3::new(33.0, 65.0, 119.0));
+}
+
 #[test]
 fn test_div() {
     {
@@ -478,6 +515,26 @@ fn test_cross() {
     assert_eq!(v_1.cross(&v_2), Vec3::new(-6.0, 26.0, -16.0));
 }
 
+#[test]
+fn test_reflect() {
+    let v = Vec3::new(1.0, -1.0, 0.0);
+    let n = Vec3::new(0.0, 1.0, 0.0);
+    assert_eq!(v.reflect(&n), Vec3::new(1.0, 1.0, 0.0));
+}
+
+#[test]
+fn test_refract() {
+    let v = Vec3::new(1.0, -1.0, 0.0).unit();
+    let n = Vec3::new(0.0, 1.0, 0.0);
+    // same index on both sides, no bending
+    let r = v.refract(&n, 1.0);
+    assert!((&r - &v).length() < 1e-12);
+    // into a denser medium, bends towards the normal
+    let r = v.refract(&n, 1.0 / 1.5);
+    assert!(r.x < v.x && r.y < v.y);
+    assert!((r.length() - 1.0).abs() < 1e-12);
+}
+
 // #[test] 如何优雅地判断float？
 // fn test_unit() {
 //     let v = Vec3::new(3.0, 4.0, 0.0);
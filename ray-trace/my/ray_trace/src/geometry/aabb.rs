@@ -1,7 +1,8 @@
 use crate::common::ray::Ray;
-use crate::common::vec3::Point3;
+use crate::common::vec3::{Point3, Vec3};
 use std::ops::{BitOr, BitOrAssign, Range};
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, Clone)]
 pub struct AABB {
     min: Point3,
@@ -20,13 +21,44 @@ impl AABB {
         &self.max
     }
 
+    pub fn centroid(&self) -> Point3 {
+        (&self.min + &self.max) * 0.5
+    }
+
+    pub fn surface_area(&self) -> f64 {
+        let d = &self.max - &self.min;
+        2.0 * (d.x * d.y + d.y * d.z + d.z * d.x)
+    }
+
+    // index of the longest side, 0 for x, 1 for y, 2 for z
+    pub fn longest_axis(&self) -> usize {
+        let d = &self.max - &self.min;
+        if d.x > d.y && d.x > d.z {
+            0
+        } else if d.y > d.z {
+            1
+        } else {
+            2
+        }
+    }
+
     pub fn hit(&self, ray: &Ray, unit_limit: Range<f64>) -> bool {
+        let inv_direction = Vec3::new(
+            1.0 / ray.direction.x,
+            1.0 / ray.direction.y,
+            1.0 / ray.direction.z,
+        );
+        self.hit_inv(ray, &inv_direction, unit_limit)
+    }
+
+    // same as `hit`, with `1 / ray.direction` computed once by the caller
+    pub fn hit_inv(&self, ray: &Ray, inv_direction: &Vec3, unit_limit: Range<f64>) -> bool {
         let mut t_min = unit_limit.start;
         let mut t_max = unit_limit.end;
         for i in 0..3 {
             // TODO: when inv = Inf and min - origin = 0, the calculation will give a NaN
             // TODO 不明白，这是在算啥？
-            let inv = 1.0 / ray.direction[i];
+            let inv = inv_direction[i];
             let mut t0 = (self.min[i] - ray.origin[i]) * inv;
             let mut t1 = (self.max[i] - ray.origin[i]) * inv;
             if inv < 0.0 {
@@ -1,98 +1,406 @@
 use crate::common::ray::Ray;
-use crate::common::vec3::Point3;
+use crate::common::vec3::{Point3, Vec3};
 use crate::geometry::aabb::AABB;
 use crate::geometry::hit::HitRecord;
 use crate::geometry::Geometry;
 use std::fmt::{Debug, Formatter};
-use std::ops::{BitOr, Range};
+use std::ops::Range;
 
+// how a node decides where to split its objects
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum BvhBuildStrategy {
+    // cut the centroid bounds in half along the longest axis
+    Midpoint,
+    // sort along the longest axis, half of the objects go to each side
+    #[default]
+    Median,
+    // binned surface area heuristic, cheapest estimated traversal cost wins
+    SurfaceAreaHeuristic,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BvhStats {
+    pub depth: usize,
+    pub leaf_count: usize,
+    pub node_count: usize,
+}
+
+impl BvhStats {
+    fn leaf() -> Self {
+        Self {
+            depth: 1,
+            leaf_count: 1,
+            node_count: 1,
+        }
+    }
+
+    fn parent_of(left: &Self, right: &Self) -> Self {
+        Self {
+            depth: left.depth.max(right.depth) + 1,
+            leaf_count: left.leaf_count + right.leaf_count,
+            node_count: left.node_count + right.node_count + 1,
+        }
+    }
+}
+
+enum BvhNodeKind {
+    // objects[start..start + count]
+    Leaf { start: usize, count: usize },
+    // left child is the next node, right child is nodes[right]
+    Interior { right: usize, axis: usize },
+}
+
+struct BvhNode {
+    bbox: AABB,
+    kind: BvhNodeKind,
+}
+
+// nodes are stored in depth-first order, so the traversal walks an array
+// instead of chasing boxes
 #[derive(Default)]
 pub struct BoundingVolumeHierachies {
-    bbox: Option<AABB>,
-    left: Option<Box<dyn Geometry>>,
-    right: Option<Box<dyn Geometry>>,
+    nodes: Vec<BvhNode>,
+    objects: Vec<Box<dyn Geometry>>,
+    stats: BvhStats,
 }
 
 impl Debug for BoundingVolumeHierachies {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        f.write_fmt(format_args!("BVH {{ box: {:?} }}", self.bbox))
+        f.write_fmt(format_args!(
+            "BVH {{ box: {:?}, stats: {:?} }}",
+            self.nodes.first().map(|node| &node.bbox),
+            self.stats
+        ))
     }
 }
 
+struct BuildItem {
+    bbox: AABB,
+    centroid: Point3,
+    object: Box<dyn Geometry>,
+}
+
+const SAH_BINS: usize = 12;
+// traversal stack size, deeper nodes are built with median split to stay below it
+const MAX_DEPTH: usize = 64;
+
 impl BoundingVolumeHierachies {
-    pub fn new(objects: Vec<Box<dyn Geometry>>, time_limit: Range<f64>) -> Self {
+    pub fn new(
+        objects: Vec<Box<dyn Geometry>>,
+        time_limit: Range<f64>,
+        strategy: BvhBuildStrategy,
+    ) -> Self {
         if objects.is_empty() {
             Self::default()
         } else {
-            let mut objects: Vec<_> = objects.into_iter().map(Some).collect();
-            let count = objects.len();
-            Self::new_internal(&mut objects, 0..count, time_limit)
+            let items = objects
+                .into_iter()
+                .map(|object| {
+                    let bbox = object
+                        .bbox(time_limit.clone())
+                        .expect("No bounding box in bvh_node constructor.");
+                    BuildItem {
+                        centroid: bbox.centroid(),
+                        bbox,
+                        object,
+                    }
+                })
+                .collect();
+            let mut bvh = Self::default();
+            bvh.stats = bvh.build_node(items, strategy, 1);
+            bvh
         }
     }
 
-    fn new_internal(
-        objects: &mut Vec<Option<Box<dyn Geometry>>>,
-        index: Range<usize>,
-        time_limit: Range<f64>,
-    ) -> Self {
-        let count = index.end - index.start;
-
-        if count == 1 {
-            let left = objects[index.start].take().unwrap();
-            let bbox = left
-                .bbox(time_limit)
-                .expect("No bounding box in bvh_node constructor.");
-            Self {
-                bbox: Some(bbox),
-                left: Some(left),
-                right: None,
+    pub const fn stats(&self) -> &BvhStats {
+        &self.stats
+    }
+
+    // append the subtree of `items` in depth-first order
+    fn build_node(
+        &mut self,
+        mut items: Vec<BuildItem>,
+        strategy: BvhBuildStrategy,
+        depth: usize,
+    ) -> BvhStats {
+        let bbox = items
+            .iter()
+            .map(|item| item.bbox.clone())
+            .reduce(|acc, bbox| acc | bbox)
+            .unwrap();
+
+        if items.len() <= 2 {
+            self.nodes.push(BvhNode {
+                bbox,
+                kind: BvhNodeKind::Leaf {
+                    start: self.objects.len(),
+                    count: items.len(),
+                },
+            });
+            self.objects
+                .extend(items.into_iter().map(|item| item.object));
+            return BvhStats::leaf();
+        }
+
+        let strategy = if depth >= MAX_DEPTH / 2 {
+            BvhBuildStrategy::Median
+        } else {
+            strategy
+        };
+        let (axis, right_items) = match strategy {
+            BvhBuildStrategy::Midpoint => Self::split_midpoint(&mut items),
+            BvhBuildStrategy::Median => Self::split_median(&mut items),
+            BvhBuildStrategy::SurfaceAreaHeuristic => Self::split_sah(&mut items),
+        };
+
+        let index = self.nodes.len();
+        self.nodes.push(BvhNode {
+            bbox,
+            kind: BvhNodeKind::Interior { right: 0, axis },
+        });
+        let left_stats = self.build_node(items, strategy, depth + 1);
+        let right = self.nodes.len();
+        self.nodes[index].kind = BvhNodeKind::Interior { right, axis };
+        let right_stats = self.build_node(right_items, strategy, depth + 1);
+
+        BvhStats::parent_of(&left_stats, &right_stats)
+    }
+
+    fn centroid_bounds(items: &[BuildItem]) -> AABB {
+        items
+            .iter()
+            .map(|item| AABB::new(item.centroid.clone(), item.centroid.clone()))
+            .reduce(|acc, bbox| acc | bbox)
+            .unwrap()
+    }
+
+    // keep the first part in `items`, return the split axis and the second part
+    fn split_median(items: &mut Vec<BuildItem>) -> (usize, Vec<BuildItem>) {
+        let axis = Self::centroid_bounds(items).longest_axis();
+        items.sort_by(|a, b| a.centroid[axis].partial_cmp(&b.centroid[axis]).unwrap());
+        (axis, items.split_off(items.len() / 2))
+    }
+
+    fn split_midpoint(items: &mut Vec<BuildItem>) -> (usize, Vec<BuildItem>) {
+        let bounds = Self::centroid_bounds(items);
+        let axis = bounds.longest_axis();
+        let mid = (bounds.min()[axis] + bounds.max()[axis]) / 2.0;
+
+        let (left, right): (Vec<_>, Vec<_>) = std::mem::take(items)
+            .into_iter()
+            .partition(|item| item.centroid[axis] < mid);
+        if left.is_empty() || right.is_empty() {
+            // all centroids on the same spot, nothing to cut
+            *items = left.into_iter().chain(right).collect();
+            return Self::split_median(items);
+        }
+        *items = left;
+        (axis, right)
+    }
+
+    fn split_sah(items: &mut Vec<BuildItem>) -> (usize, Vec<BuildItem>) {
+        let bounds = Self::centroid_bounds(items);
+        let bin_of = |item: &BuildItem, axis: usize| -> usize {
+            let extent = bounds.max()[axis] - bounds.min()[axis];
+            let bin =
+                ((item.centroid[axis] - bounds.min()[axis]) / extent * SAH_BINS as f64) as usize;
+            bin.min(SAH_BINS - 1)
+        };
+
+        // (cost, axis, last bin of the left part)
+        let mut best: Option<(f64, usize, usize)> = None;
+        for axis in 0..3 {
+            if bounds.max()[axis] - bounds.min()[axis] <= 0.0 {
+                continue;
+            }
+
+            let mut bins: Vec<(usize, Option<AABB>)> = vec![(0, None); SAH_BINS];
+            for item in items.iter() {
+                let (count, bbox) = &mut bins[bin_of(item, axis)];
+                *count += 1;
+                *bbox = Some(match bbox.take() {
+                    Some(bbox) => bbox | &item.bbox,
+                    None => item.bbox.clone(),
+                });
             }
-        } else if count == 2 {
-            let left = objects[index.start].take().unwrap();
-            let right = objects[index.start + 1].take().unwrap();
-            let left_bbox = left
-                .bbox(time_limit.clone())
-                .expect("No bounding box in bvh_node constructor.");
-            let right_bbox = right
-                .bbox(time_limit.clone())
-                .expect("No bounding box in bvh_node constructor.");
-
-            Self {
-                bbox: Some(left_bbox | right_bbox),
-                left: Some(left),
-                right: Some(right),
+
+            // sweep from the right, so the left sweep can price every split at once
+            let mut right_costs = [0.0; SAH_BINS];
+            let mut count = 0;
+            let mut bbox: Option<AABB> = None;
+            for split in (1..SAH_BINS).rev() {
+                Self::grow(&mut count, &mut bbox, &bins[split]);
+                right_costs[split - 1] = Self::area_count(count, &bbox);
+            }
+
+            let mut count = 0;
+            let mut bbox: Option<AABB> = None;
+            for split in 0..SAH_BINS - 1 {
+                Self::grow(&mut count, &mut bbox, &bins[split]);
+                let cost = Self::area_count(count, &bbox) + right_costs[split];
+                if best.is_none_or(|(best_cost, _, _)| cost < best_cost) {
+                    best = Some((cost, axis, split));
+                }
             }
-        } else {
-            unimplemented!()
+        }
+
+        let (axis, split) = match best {
+            Some((_, axis, split)) => (axis, split),
+            None => return Self::split_median(items),
+        };
+        let (left, right): (Vec<_>, Vec<_>) = std::mem::take(items)
+            .into_iter()
+            .partition(|item| bin_of(item, axis) <= split);
+        if left.is_empty() || right.is_empty() {
+            *items = left.into_iter().chain(right).collect();
+            return Self::split_median(items);
+        }
+        *items = left;
+        (axis, right)
+    }
+
+    fn grow(count: &mut usize, bbox: &mut Option<AABB>, bin: &(usize, Option<AABB>)) {
+        *count += bin.0;
+        if let Some(bin_bbox) = &bin.1 {
+            *bbox = Some(match bbox.take() {
+                Some(bbox) => bbox | bin_bbox,
+                None => bin_bbox.clone(),
+            });
         }
     }
+
+    // the part of the SAH cost one side of a split contributes
+    fn area_count(count: usize, bbox: &Option<AABB>) -> f64 {
+        bbox.as_ref()
+            .map_or(0.0, |bbox| bbox.surface_area() * count as f64)
+    }
 }
 
 impl Geometry for BoundingVolumeHierachies {
     fn hit(&self, ray: &Ray, unit_limit: Range<f64>) -> Option<HitRecord<'_>> {
-        let bbox = self.bbox.as_ref()?;
-        if !bbox.hit(ray, unit_limit.clone()) {
+        if self.nodes.is_empty() {
             return None;
         }
 
-        let hit_left = self
-            .left
-            .as_ref()
-            .and_then(|left| left.hit(ray, unit_limit.clone()));
-        let hit_right = self.right.as_ref().and_then(|right| {
-            let right_limit = unit_limit.start
-                ..hit_left
-                    .as_ref()
-                    .map_or(unit_limit.end, |record| record.unit);
-            right.hit(ray, right_limit)
-        });
+        let inv_direction = Vec3::new(
+            1.0 / ray.direction.x,
+            1.0 / ray.direction.y,
+            1.0 / ray.direction.z,
+        );
+        let mut closest = unit_limit.end;
+        let mut result = None;
+
+        let mut stack = [0; MAX_DEPTH];
+        let mut stack_len = 0;
+        let mut index = 0;
+        loop {
+            let node = &self.nodes[index];
+            if node
+                .bbox
+                .hit_inv(ray, &inv_direction, unit_limit.start..closest)
+            {
+                match node.kind {
+                    BvhNodeKind::Leaf { start, count } => {
+                        for object in &self.objects[start..start + count] {
+                            if let Some(record) = object.hit(ray, unit_limit.start..closest) {
+                                closest = record.unit;
+                                result = Some(record);
+                            }
+                        }
+                    }
+                    BvhNodeKind::Interior { right, axis } => {
+                        // visit the nearer child first, the farther one is
+                        // likely culled by the shrunk `closest`
+                        if inv_direction[axis] < 0.0 {
+                            stack[stack_len] = index + 1;
+                            index = right;
+                        } else {
+                            stack[stack_len] = right;
+                            index += 1;
+                        }
+                        stack_len += 1;
+                        continue;
+                    }
+                }
+            }
 
-        // TODO 不明白
-        // Right has small t then left if it return `Some`, so right appear first
-        hit_right.or(hit_left)
+            if stack_len == 0 {
+                break;
+            }
+            stack_len -= 1;
+            index = stack[stack_len];
+        }
+
+        result
     }
 
     fn bbox(&self, _time_limit: Range<f64>) -> Option<AABB> {
-        self.bbox.clone()
+        self.nodes.first().map(|node| node.bbox.clone())
     }
 }
+
+////////// UT //////////
+#[cfg(test)]
+fn spheres(count: usize) -> Vec<Box<dyn Geometry>> {
+    use crate::common::color::Color;
+    use crate::geometry::sphere::Sphere;
+    use crate::material::lambertian::Lambertian;
+
+    (0..count)
+        .map(|i| -> Box<dyn Geometry> {
+            Box::new(Sphere::new(
+                Point3::new(i as f64 * 3.0, 0.0, -5.0),
+                1.0,
+                Lambertian::new(Color::newf(0.5, 0.5, 0.5)),
+            ))
+        })
+        .collect()
+}
+
+#[test]
+fn test_hit_many_objects() {
+    for strategy in [
+        BvhBuildStrategy::Midpoint,
+        BvhBuildStrategy::Median,
+        BvhBuildStrategy::SurfaceAreaHeuristic,
+    ] {
+        let bvh = BoundingVolumeHierachies::new(spheres(7), 0.0..0.0, strategy);
+
+        for i in 0..7 {
+            let ray = Ray::new(
+                Point3::new(i as f64 * 3.0, 0.0, 0.0),
+                Vec3::new(0.0, 0.0, -1.0),
+            );
+            let hit = bvh.hit(&ray, 0.001..f64::INFINITY).unwrap();
+            assert_eq!(hit.point, Point3::new(i as f64 * 3.0, 0.0, -4.0));
+        }
+        let ray = Ray::new(Point3::new(1.5, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(bvh.hit(&ray, 0.001..f64::INFINITY).is_none());
+    }
+}
+
+#[test]
+fn test_stats() {
+    let bvh = BoundingVolumeHierachies::new(spheres(8), 0.0..0.0, BvhBuildStrategy::Median);
+    assert_eq!(
+        bvh.stats(),
+        &BvhStats {
+            depth: 3,
+            leaf_count: 4,
+            node_count: 7,
+        }
+    );
+}
+
+#[test]
+fn test_hit_closest_in_both_directions() {
+    let bvh = BoundingVolumeHierachies::new(spheres(9), 0.0..0.0, BvhBuildStrategy::Median);
+
+    let ray = Ray::new(Point3::new(-10.0, 0.0, -5.0), Vec3::new(1.0, 0.0, 0.0));
+    let hit = bvh.hit(&ray, 0.001..f64::INFINITY).unwrap();
+    assert_eq!(hit.point, Point3::new(-1.0, 0.0, -5.0));
+
+    let ray = Ray::new(Point3::new(40.0, 0.0, -5.0), Vec3::new(-1.0, 0.0, 0.0));
+    let hit = bvh.hit(&ray, 0.001..f64::INFINITY).unwrap();
+    assert_eq!(hit.point, Point3::new(25.0, 0.0, -5.0));
+}
@@ -26,13 +26,25 @@ impl Debug for HitRecord<'_> {
 impl<'m> HitRecord<'m> {
     pub fn new<G: Geometry>(r: &Ray, obj: &'m G, unit: f64) -> Self {
         let point = r.at(unit);
-        let mut normal = obj.normal(&point);
+        let normal = obj.normal(&point);
+        let uv = obj.uv(&point);
+        Self::from_parts(r, unit, normal, obj.material(), uv)
+    }
+
+    // for geometry whose normal and uv depend on more than the hit point,
+    // `normal` points outwards
+    pub fn from_parts(
+        r: &Ray,
+        unit: f64,
+        mut normal: Vec3,
+        material: &'m dyn Material,
+        (u, v): (f64, f64),
+    ) -> Self {
+        let point = r.at(unit);
         let outside = r.direction.dot(&normal) < 0.0;
         if !outside {
             normal.reverse();
         }
-        let material = obj.material();
-        let (u, v) = obj.uv(&point);
         Self {
             point,
             normal,
@@ -1,22 +1,25 @@
 use crate::common::ray::Ray;
 use crate::geometry::aabb::AABB;
-use crate::geometry::bvh::BoundingVolumeHierachies;
+use crate::geometry::bvh::{BoundingVolumeHierachies, BvhBuildStrategy};
 use crate::geometry::hit::HitRecord;
 use crate::geometry::world::World;
 use crate::geometry::Geometry;
+use log::info;
 use std::fmt::{Debug, Formatter};
 use std::ops::Range;
 
 #[derive(Default)]
 pub struct GeometryList {
     objects: Vec<Box<dyn Geometry>>,
+    strategy: BvhBuildStrategy,
 }
 
 impl Debug for GeometryList {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         f.write_fmt(format_args!(
-            "GeometryList {{ objects: {}}}",
-            self.objects.len()
+            "GeometryList {{ objects: {}, strategy: {:?} }}",
+            self.objects.len(),
+            self.strategy
         ))
     }
 }
@@ -37,8 +40,15 @@ impl GeometryList {
         self.objects.clear();
     }
 
+    pub fn bvh_strategy(&mut self, strategy: BvhBuildStrategy) -> &mut Self {
+        self.strategy = strategy;
+        self
+    }
+
     pub fn build(self, time_limit: Range<f64>) -> World {
-        World::new(BoundingVolumeHierachies::new(self.objects, time_limit))
+        let bvh = BoundingVolumeHierachies::new(self.objects, time_limit, self.strategy);
+        info!("BVH built with {:?}: {:?}", self.strategy, bvh.stats());
+        World::new(bvh)
     }
 }
 
@@ -59,7 +69,10 @@ impl Geometry for GeometryList {
 
         for object in &self.objects {
             let bbox = object.bbox(time_limit.clone())?;
-            result = result.map(|last| last | &bbox).or_else(|| Some(bbox))
+            result = Some(match result {
+                Some(last) => last | &bbox,
+                None => bbox,
+            });
         }
         result
     }
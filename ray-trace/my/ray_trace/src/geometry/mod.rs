@@ -6,16 +6,17 @@ use crate::material::Material;
 use std::ops::Range;
 
 mod aabb;
-mod bvh;
-pub(crate) mod hit;
-pub(crate) mod list;
-pub(crate) mod sphere;
-pub(crate) mod world;
+pub mod bvh;
+pub mod hit;
+pub mod list;
+pub mod moving_sphere;
+pub mod sphere;
+pub mod world;
 
 // TODO Send+Sync
 pub trait Geometry: Send + Sync {
     // 法向量
-    fn normal(&self, p: &Point3) -> Vec3 {
+    fn normal(&self, _p: &Point3) -> Vec3 {
         unimplemented!(
             "{}'s normal function should not be called directly",
             std::any::type_name::<Self>()
@@ -30,7 +31,7 @@ pub trait Geometry: Send + Sync {
         )
     }
 
-    fn uv(&self, point: &Point3) -> (f64, f64) {
+    fn uv(&self, _point: &Point3) -> (f64, f64) {
         unimplemented!(
             "{}'s uv function should not be called directly",
             std::any::type_name::<Self>()
@@ -0,0 +1,105 @@
+use crate::common::ray::Ray;
+use crate::common::vec3::{Point3, Vec3};
+use crate::geometry::aabb::AABB;
+use crate::geometry::hit::HitRecord;
+use crate::geometry::sphere::{sphere_hit_unit, sphere_uv};
+use crate::geometry::Geometry;
+use crate::material::Material;
+use std::ops::Range;
+
+// a sphere moving in a straight line from `centers.0` at `time.start` to
+// `centers.1` at `time.end`, standing still before and after
+pub struct MovingSphere<M: Material> {
+    centers: (Point3, Point3),
+    time: Range<f64>,
+    radius: f64,
+    material: M,
+}
+
+impl<M: Material> MovingSphere<M> {
+    pub fn new(centers: (Point3, Point3), time: Range<f64>, radius: f64, material: M) -> Self {
+        Self {
+            centers,
+            time,
+            radius,
+            material,
+        }
+    }
+
+    pub fn center(&self, time: f64) -> Point3 {
+        let duration = self.time.end - self.time.start;
+        if duration <= 0.0 {
+            return self.centers.0.clone();
+        }
+        let t = ((time - self.time.start) / duration).clamp(0.0, 1.0);
+        &self.centers.0 + t * (&self.centers.1 - &self.centers.0)
+    }
+
+    fn bbox_at(&self, time: f64) -> AABB {
+        let radius = self.radius.abs();
+        let center = self.center(time);
+        AABB::new(
+            &center - Vec3::new(radius, radius, radius),
+            &center + Vec3::new(radius, radius, radius),
+        )
+    }
+}
+
+impl<M: Material> Geometry for MovingSphere<M> {
+    fn material(&self) -> &dyn Material {
+        &self.material
+    }
+
+    // normal and uv depend on when, so the hit record is built here
+    fn hit(&self, ray: &Ray, unit_limit: Range<f64>) -> Option<HitRecord<'_>> {
+        let center = self.center(ray.time);
+        let unit = sphere_hit_unit(&center, self.radius, ray, unit_limit)?;
+        let point = ray.at(unit);
+        let normal = (&point - &center) / self.radius;
+        let uv = sphere_uv(&center, &point);
+        Some(HitRecord::from_parts(ray, unit, normal, &self.material, uv))
+    }
+
+    // the center moves along a segment, the boxes at both ends cover the path
+    fn bbox(&self, time_limit: Range<f64>) -> Option<AABB> {
+        Some(self.bbox_at(time_limit.start) | self.bbox_at(time_limit.end))
+    }
+}
+
+////////// UT //////////
+#[cfg(test)]
+fn moving() -> MovingSphere<crate::material::lambertian::Lambertian<crate::common::color::Color>> {
+    use crate::common::color::Color;
+    use crate::material::lambertian::Lambertian;
+    MovingSphere::new(
+        (Point3::new(0.0, 0.0, -5.0), Point3::new(2.0, 0.0, -5.0)),
+        0.0..1.0,
+        0.5,
+        Lambertian::new(Color::newf(0.5, 0.5, 0.5)),
+    )
+}
+
+#[test]
+fn test_center() {
+    let sphere = moving();
+    assert_eq!(sphere.center(0.5), Point3::new(1.0, 0.0, -5.0));
+    assert_eq!(sphere.center(-1.0), Point3::new(0.0, 0.0, -5.0));
+    assert_eq!(sphere.center(3.0), Point3::new(2.0, 0.0, -5.0));
+}
+
+#[test]
+fn test_hit_by_time() {
+    let sphere = moving();
+    let ray = |time| Ray::new(Point3::new(2.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0)).time(time);
+    assert!(sphere.hit(&ray(0.0), 0.001..f64::INFINITY).is_none());
+    let hit = sphere.hit(&ray(1.0), 0.001..f64::INFINITY).unwrap();
+    assert!((hit.unit - 4.5).abs() < 1e-12);
+    assert_eq!(hit.normal, Vec3::new(0.0, 0.0, 1.0));
+}
+
+#[test]
+fn test_bbox_covers_path() {
+    let bbox = moving().bbox(0.0..1.0).unwrap();
+    assert_eq!(bbox.min(), &Point3::new(-0.5, -0.5, -5.5));
+    assert_eq!(bbox.max(), &Point3::new(2.5, 0.5, -4.5));
+}
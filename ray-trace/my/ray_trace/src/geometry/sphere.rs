@@ -6,7 +6,6 @@ use crate::geometry::Geometry;
 use crate::material::Material;
 use std::f64::consts::PI;
 use std::ops::Range;
-use std::os::macos::raw::time_t;
 
 pub struct Sphere<M: Material> {
     center: Point3,
@@ -25,52 +24,27 @@ impl<M: Material> Sphere<M> {
 }
 
 impl<M: Material> Geometry for Sphere<M> {
+    // divide by the signed radius, a negative radius flips the normal inwards,
+    // which makes a hollow sphere when nested in a dielectric one
     fn normal(&self, p: &Point3) -> Vec3 {
-        (p - &self.center).unit()
+        (p - &self.center) / self.radius
     }
 
     fn material(&self) -> &dyn Material {
         &self.material
     }
 
-    // hongfendong 不明白
     fn uv(&self, point: &Point3) -> (f64, f64) {
-        let point = (point - &self.center).unit();
-        let phi = (-point.z).atan2(point.x); // [-pi, pi]
-        let theta = point.y.asin(); // [-pi / 2 , pi / 2]
-        let u = phi / 2.0 / PI + 0.5;
-        let v = theta / PI + 0.5;
-        (u, v)
+        sphere_uv(&self.center, point)
     }
 
     fn hit(&self, ray: &Ray, unit_limit: Range<f64>) -> Option<HitRecord<'_>> {
-        // Ray = A + t*B
-        // t^2 * b * b + 2t*b*(A-C) + (A-C)*(A-C) - r^2 = 0
-        let oc = &ray.origin - &self.center;
-        let a = ray.direction.length_squared();
-        let half_b = oc.dot(&ray.direction);
-        let c = oc.length_squared() - self.radius * self.radius;
-
-        let discriminant = half_b * half_b - a * c;
-        if discriminant < 0.0 {
-            return None;
-        }
-        let sqrt = discriminant.sqrt();
-
-        let root_1 = (-half_b - sqrt) / a;
-        if unit_limit.contains(&root_1) {
-            return Some(HitRecord::new(ray, self, root_1));
-        }
-
-        let root_2 = (-half_b + sqrt) / a;
-        if unit_limit.contains(&root_2) {
-            return Some(HitRecord::new(ray, self, root_2));
-        }
-
-        None
+        let unit = sphere_hit_unit(&self.center, self.radius, ray, unit_limit)?;
+        Some(HitRecord::new(ray, self, unit))
     }
 
-    fn bbox(&self, time_limit: Range<f64>) -> Option<AABB> {
+    fn bbox(&self, _time_limit: Range<f64>) -> Option<AABB> {
+        let radius = self.radius.abs();
         Some(
             // TODO if
             if false {
@@ -78,10 +52,53 @@ impl<M: Material> Geometry for Sphere<M> {
             } else {
                 // TODO time_limit
                 AABB::new(
-                    &self.center - Vec3::new(self.radius, self.radius, self.radius),
-                    &self.center + Vec3::new(self.radius, self.radius, self.radius),
+                    &self.center - Vec3::new(radius, radius, radius),
+                    &self.center + Vec3::new(radius, radius, radius),
                 )
             },
         )
     }
 }
+
+// nearest `unit` in `unit_limit` where `ray` meets the sphere
+pub(crate) fn sphere_hit_unit(
+    center: &Point3,
+    radius: f64,
+    ray: &Ray,
+    unit_limit: Range<f64>,
+) -> Option<f64> {
+    // Ray = A + t*B
+    // t^2 * b * b + 2t*b*(A-C) + (A-C)*(A-C) - r^2 = 0
+    let oc = &ray.origin - center;
+    let a = ray.direction.length_squared();
+    let half_b = oc.dot(&ray.direction);
+    let c = oc.length_squared() - radius * radius;
+
+    let discriminant = half_b * half_b - a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    let sqrt = discriminant.sqrt();
+
+    let root_1 = (-half_b - sqrt) / a;
+    if unit_limit.contains(&root_1) {
+        return Some(root_1);
+    }
+
+    let root_2 = (-half_b + sqrt) / a;
+    if unit_limit.contains(&root_2) {
+        return Some(root_2);
+    }
+
+    None
+}
+
+// hongfendong 不明白
+pub(crate) fn sphere_uv(center: &Point3, point: &Point3) -> (f64, f64) {
+    let point = (point - center).unit();
+    let phi = (-point.z).atan2(point.x); // [-pi, pi]
+    let theta = point.y.asin(); // [-pi / 2 , pi / 2]
+    let u = phi / 2.0 / PI + 0.5;
+    let v = theta / PI + 0.5;
+    (u, v)
+}
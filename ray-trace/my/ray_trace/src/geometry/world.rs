@@ -1,7 +1,7 @@
 use crate::common::color::Color;
 use crate::common::ray::Ray;
 use crate::geometry::aabb::AABB;
-use crate::geometry::bvh::BoundingVolumeHierachies;
+use crate::geometry::bvh::{BoundingVolumeHierachies, BvhStats};
 use crate::geometry::hit::HitRecord;
 use crate::geometry::Geometry;
 use std::fmt::{Debug, Formatter};
@@ -18,6 +18,11 @@ pub fn default_background(ray: &Ray) -> Color {
     Color::newf(1.0, 1.0, 1.0).gradient(t, Color::newf(0.5, 0.7, 1.0))
 }
 
+// no ambient light, the scene is lit by emissive materials only
+pub fn black_background(_ray: &Ray) -> Color {
+    Color::newf(0.0, 0.0, 0.0)
+}
+
 impl Debug for World {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         f.write_str("World {}")
@@ -39,6 +44,10 @@ impl World {
         self.bg_func = Box::new(f);
     }
 
+    pub const fn bvh_stats(&self) -> &BvhStats {
+        self.bvh.stats()
+    }
+
     pub fn background(&self, ray: &Ray) -> Color {
         let f = &self.bg_func;
         f(ray)
@@ -0,0 +1,8 @@
+pub mod common;
+pub mod geometry;
+pub mod material;
+pub mod render;
+pub mod texture;
+
+pub use crate::render::image_sink::{ImageSink, VecSink};
+pub use crate::render::take_photo_settings::TakePhotoSettings;
@@ -1,45 +1,9 @@
-mod common;
-mod geometry;
-mod material;
-mod render;
-mod texture;
-
-use crate::common::color::Color;
-use crate::common::ray::Ray;
-use crate::common::vec3::{Point3, Vec3};
-use crate::geometry::list::GeometryList;
-use crate::geometry::sphere::Sphere;
-use crate::geometry::world::default_background;
-use crate::material::lambertian::{Lambertian, LambertianMathType};
-use crate::render::camera::CameraBuilder;
-
-// use crate::render;
-fn hit_sphere(center: &Point3, radius: f64, ray: &Ray) -> Option<(f64, Vec3)> {
-    // ||(P - C)|| == R
-    let oc = &ray.origin - center;
-    let a = ray.direction.length_squared();
-    let half_b = oc.dot(&ray.direction);
-    let c = oc.length_squared() - radius * radius;
-    let discriminant = half_b * half_b - a * c;
-    // dbg!(center, radius, ray, a, half_b, c);
-    if discriminant < 0.0 {
-        None
-    } else {
-        let t1 = (-half_b - discriminant.sqrt()) / a;
-        let t2 = (-half_b + discriminant.sqrt()) / a;
-        if t1 > 0.0 {
-            let p = ray.at(t1);
-            let n = (p - center) / radius;
-            Some((t1, n))
-        } else if t2 > 0.0 {
-            let p = ray.at(t2);
-            let n = (p - center) / radius;
-            Some((t2, n))
-        } else {
-            None
-        }
-    }
-}
+use ray_trace::common::color::Color;
+use ray_trace::common::vec3::Point3;
+use ray_trace::geometry::list::GeometryList;
+use ray_trace::geometry::sphere::Sphere;
+use ray_trace::material::lambertian::{Lambertian, LambertianMathType};
+use ray_trace::render::camera::CameraBuilder;
 
 fn main() {
     const ASPECT_RATIO: f64 = 2.00;
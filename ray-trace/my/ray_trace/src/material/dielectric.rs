@@ -0,0 +1,76 @@
+use crate::common::color::Color;
+use crate::common::random;
+use crate::common::ray::Ray;
+use crate::geometry::hit::HitRecord;
+use crate::material::{Material, ScatterRecord};
+
+// glass, water, diamond... anything clear that both reflects and refracts
+#[derive(Debug, Clone)]
+pub struct Dielectric {
+    refractive_index: f64,
+}
+
+impl Dielectric {
+    pub const fn new(refractive_index: f64) -> Self {
+        Self { refractive_index }
+    }
+
+    // Schlick's approximation of the Fresnel reflectance
+    fn reflectance(cos_theta: f64, ratio: f64) -> f64 {
+        let r0 = (1.0 - ratio) / (1.0 + ratio);
+        let r0 = r0 * r0;
+        r0 + (1.0 - r0) * (1.0 - cos_theta).powi(5)
+    }
+}
+
+impl Material for Dielectric {
+    fn scatter(&self, ray: &Ray, hit: HitRecord<'_>) -> Option<ScatterRecord> {
+        let ratio = if hit.outside {
+            1.0 / self.refractive_index
+        } else {
+            self.refractive_index
+        };
+
+        let unit = ray.direction.unit();
+        let cos_theta = (-&unit).dot(&hit.normal).min(1.0);
+        let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+
+        // total internal reflection when snell's law has no solution
+        let cannot_refract = ratio * sin_theta > 1.0;
+        let direction = if cannot_refract
+            || Self::reflectance(cos_theta, ratio) > random::gen_range(0.0, 1.0)
+        {
+            unit.reflect(&hit.normal)
+        } else {
+            unit.refract(&hit.normal, ratio)
+        };
+
+        Some(ScatterRecord {
+            color: Color::newf(1.0, 1.0, 1.0),
+            ray: Ray::new(hit.point, direction).time(ray.time),
+        })
+    }
+}
+
+////////// UT //////////
+#[test]
+fn test_total_internal_reflection() {
+    use crate::common::vec3::{Point3, Vec3};
+
+    let glass = Dielectric::new(1.5);
+    // leaving the glass at a grazing angle
+    let ray = Ray::new(Point3::new(-1.0, 0.1, 0.0), Vec3::new(1.0, -0.1, 0.0));
+    for _ in 0..16 {
+        let hit = HitRecord {
+            point: Point3::default(),
+            normal: Vec3::new(0.0, 1.0, 0.0),
+            material: &glass,
+            unit: 1.0,
+            u: 0.0,
+            v: 0.0,
+            outside: false,
+        };
+        let scattered = glass.scatter(&ray, hit).unwrap();
+        assert!(scattered.ray.direction.y > 0.0);
+    }
+}
@@ -0,0 +1,52 @@
+use crate::common::vec3::{Point3, Vec3};
+use crate::material::Material;
+use crate::texture::Texture;
+
+// an area light, emits the texture color and scatters nothing
+#[derive(Debug, Clone)]
+pub struct DiffuseLight<T: Texture> {
+    texture: T,
+    intensity: f64,
+}
+
+impl<T: Texture> DiffuseLight<T> {
+    pub fn new(texture: T) -> Self {
+        Self {
+            texture,
+            intensity: 1.0,
+        }
+    }
+
+    pub fn intensity(mut self, intensity: f64) -> Self {
+        self.intensity = intensity;
+        self
+    }
+}
+
+impl<T: Texture> Material for DiffuseLight<T> {
+    fn emitted(&self, u: f64, v: f64, point: &Point3) -> Option<Vec3> {
+        let color: Vec3 = self.texture.color(u, v, point).into();
+        Some(color * self.intensity)
+    }
+}
+
+////////// UT //////////
+#[test]
+fn test_emitted() {
+    use crate::common::color::Color;
+    use std::sync::Arc;
+
+    let light = DiffuseLight::new(Color::newf(1.0, 0.5, 0.25)).intensity(2.0);
+    let point = Point3::new(3.0, 5.0, 7.0);
+    assert_eq!(
+        light.emitted(0.0, 0.0, &point),
+        Some(Vec3::new(2.0, 1.0, 0.5))
+    );
+
+    // Arc forwards to the inner material instead of making things up
+    let shared = Arc::new(light);
+    assert_eq!(
+        shared.emitted(0.0, 0.0, &point),
+        Some(Vec3::new(2.0, 1.0, 0.5))
+    );
+}
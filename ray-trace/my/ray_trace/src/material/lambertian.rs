@@ -13,11 +13,12 @@ pub enum LambertianMathType {
 
 impl LambertianMathType {
     pub fn scatter_ray(&self, ray: &Ray, hit: HitRecord<'_>) -> Ray {
-        match self {
-            Self::Approximate => Ray::new(hit.point, hit.normal + Vec3::random_in_unit_sphere()),
-            Self::True => Ray::new(hit.point, hit.normal + Vec3::random_unit()),
-            Self::Hemisphere => Ray::new(hit.point, Vec3::random_unit_dir(&hit.normal)),
-        }
+        let direction = match self {
+            Self::Approximate => &hit.normal + Vec3::random_in_unit_sphere(),
+            Self::True => &hit.normal + Vec3::random_unit(),
+            Self::Hemisphere => Vec3::random_unit_dir(&hit.normal),
+        };
+        Ray::new(hit.point, direction).time(ray.time)
     }
 }
 
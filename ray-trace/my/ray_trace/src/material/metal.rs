@@ -0,0 +1,96 @@
+use crate::common::ray::Ray;
+use crate::common::vec3::Vec3;
+use crate::geometry::hit::HitRecord;
+use crate::material::{Material, ScatterRecord};
+use crate::texture::Texture;
+
+#[derive(Debug, Clone)]
+pub struct Metal<T: Texture> {
+    texture: T,
+    fuzz: f64, // 0 is a perfect mirror
+}
+
+impl<T: Texture> Metal<T> {
+    pub fn new(texture: T) -> Self {
+        Self { texture, fuzz: 0.0 }
+    }
+
+    pub fn fuzz(mut self, fuzz: f64) -> Self {
+        self.fuzz = fuzz.clamp(0.0, 1.0);
+        self
+    }
+}
+
+impl<T: Texture> Material for Metal<T> {
+    fn scatter(&self, ray: &Ray, hit: HitRecord<'_>) -> Option<ScatterRecord> {
+        let reflected = ray.direction.unit().reflect(&hit.normal);
+        let direction = reflected + self.fuzz * Vec3::random_in_unit_sphere();
+        // fuzz pushed the ray below the surface, absorb it
+        if direction.dot(&hit.normal) <= 0.0 {
+            return None;
+        }
+
+        let color = self.texture.color(hit.u, hit.v, &hit.point);
+        Some(ScatterRecord {
+            color,
+            ray: Ray::new(hit.point, direction).time(ray.time),
+        })
+    }
+}
+
+////////// UT //////////
+#[cfg(test)]
+fn hit_floor(material: &dyn Material) -> HitRecord<'_> {
+    use crate::common::vec3::Point3;
+
+    HitRecord {
+        point: Point3::default(),
+        normal: Vec3::new(0.0, 1.0, 0.0),
+        material,
+        unit: 1.0,
+        u: 0.0,
+        v: 0.0,
+        outside: true,
+    }
+}
+
+#[test]
+fn test_mirror() {
+    use crate::common::color::Color;
+    use crate::common::vec3::Point3;
+
+    let metal = Metal::new(Color::newf(0.8, 0.6, 0.2));
+    let ray = Ray::new(Point3::new(-1.0, 1.0, 0.0), Vec3::new(2.0, -2.0, 0.0)).time(0.25);
+    let scattered = metal.scatter(&ray, hit_floor(&metal)).unwrap();
+
+    let expected = Vec3::new(1.0, 1.0, 0.0).unit();
+    assert!((&scattered.ray.direction - &expected).length() < 1e-12);
+    assert_eq!(scattered.ray.origin, Point3::default());
+    assert_eq!(scattered.ray.time, 0.25);
+    assert_eq!(Vec3::from(scattered.color), Vec3::new(0.8, 0.6, 0.2));
+}
+
+#[test]
+fn test_absorb_below_surface() {
+    use crate::common::color::Color;
+    use crate::common::random;
+    use crate::common::vec3::Point3;
+
+    // coming from under the surface, the mirror direction points back down
+    let metal = Metal::new(Color::newf(0.8, 0.8, 0.8));
+    let ray = Ray::new(Point3::new(-1.0, -1.0, 0.0), Vec3::new(1.0, 1.0, 0.0));
+    assert!(metal.scatter(&ray, hit_floor(&metal)).is_none());
+
+    // at a grazing angle the fuzz pushes some rays below, those are absorbed
+    let fuzzy = Metal::new(Color::newf(0.8, 0.8, 0.8)).fuzz(1.0);
+    let ray = Ray::new(Point3::new(-1.0, 0.01, 0.0), Vec3::new(1.0, -0.01, 0.0));
+    let mut absorbed = 0;
+    for index in 0..64 {
+        random::seed(index);
+        match fuzzy.scatter(&ray, hit_floor(&fuzzy)) {
+            Some(scattered) => assert!(scattered.ray.direction.y > 0.0),
+            None => absorbed += 1,
+        }
+    }
+    assert!(absorbed > 0 && absorbed < 64, "{} absorbed", absorbed);
+}
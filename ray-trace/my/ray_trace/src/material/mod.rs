@@ -1,4 +1,7 @@
-pub(crate) mod lambertian;
+pub mod dielectric;
+pub mod diffuse_light;
+pub mod lambertian;
+pub mod metal;
 
 use crate::common::color::Color;
 use crate::common::ray::Ray;
@@ -13,17 +16,21 @@ pub struct ScatterRecord {
 }
 
 pub trait Material: Send + Sync {
-    fn scatter(&self, ray: &Ray, hit: HitRecord<'_>) -> Option<ScatterRecord> {
+    fn scatter(&self, _ray: &Ray, _hit: HitRecord<'_>) -> Option<ScatterRecord> {
         None
     }
 
-    fn emitted(&self, u: f64, v: f64, point: &Point3) -> Option<Vec3> {
+    fn emitted(&self, _u: f64, _v: f64, _point: &Point3) -> Option<Vec3> {
         None
     }
 }
 
 impl<M: Material> Material for Arc<M> {
+    fn scatter(&self, ray: &Ray, hit: HitRecord<'_>) -> Option<ScatterRecord> {
+        self.as_ref().scatter(ray, hit)
+    }
+
     fn emitted(&self, u: f64, v: f64, point: &Point3) -> Option<Vec3> {
-        Some(point.clone())
+        self.as_ref().emitted(u, v, point)
     }
 }
@@ -0,0 +1,121 @@
+use crate::common::vec3::Radiance;
+
+// keep sampling a pixel until the standard error of its mean luminance is
+// below `threshold`, taking at least `min_samples` and at most `max_samples`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AdaptiveSampling {
+    pub min_samples: usize,
+    pub max_samples: usize,
+    pub threshold: f64,
+}
+
+impl AdaptiveSampling {
+    pub const fn new(min_samples: usize, max_samples: usize, threshold: f64) -> Self {
+        Self {
+            min_samples,
+            max_samples,
+            threshold,
+        }
+    }
+
+    pub fn done(&self, estimate: &PixelEstimate) -> bool {
+        let count = estimate.count();
+        if count < self.min_samples.max(2) {
+            return count >= self.max_samples.max(1);
+        }
+        count >= self.max_samples || estimate.standard_error() <= self.threshold
+    }
+}
+
+// running mean of the radiance and variance of the luminance, Welford's way
+#[derive(Debug, Clone, Default)]
+pub struct PixelEstimate {
+    count: usize,
+    sum: Radiance,
+    mean: f64,
+    m2: f64,
+}
+
+impl PixelEstimate {
+    pub fn add(&mut self, radiance: Radiance) {
+        let luminance = luminance(&radiance);
+        self.count += 1;
+        self.sum += radiance;
+        let delta = luminance - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (luminance - self.mean);
+    }
+
+    pub const fn count(&self) -> usize {
+        self.count
+    }
+
+    pub fn mean(&self) -> Radiance {
+        if self.count == 0 {
+            Radiance::default()
+        } else {
+            &self.sum / self.count as f64
+        }
+    }
+
+    // of the mean, so it shrinks with 1/sqrt(n)
+    pub fn standard_error(&self) -> f64 {
+        if self.count < 2 {
+            return f64::INFINITY;
+        }
+        let variance = self.m2 / (self.count - 1) as f64;
+        (variance / self.count as f64).sqrt()
+    }
+}
+
+// Rec. 709
+fn luminance(radiance: &Radiance) -> f64 {
+    0.2126 * radiance.x + 0.7152 * radiance.y + 0.0722 * radiance.z
+}
+
+// blue for few samples, through green to red for `max`
+pub fn heat(samples: usize, max: usize) -> Radiance {
+    let t = (samples as f64 / max.max(1) as f64).clamp(0.0, 1.0);
+    if t < 0.5 {
+        Radiance::new(0.0, 2.0 * t, 1.0 - 2.0 * t)
+    } else {
+        Radiance::new(2.0 * t - 1.0, 2.0 - 2.0 * t, 0.0)
+    }
+}
+
+////////// UT //////////
+#[test]
+fn test_estimate() {
+    let mut estimate = PixelEstimate::default();
+    for v in [1.0, 3.0, 1.0, 3.0] {
+        estimate.add(Radiance::new(v, v, v));
+    }
+    assert_eq!(estimate.mean(), Radiance::new(2.0, 2.0, 2.0));
+    // sample variance 4/3, over 4 samples
+    assert!((estimate.standard_error() - (1.0f64 / 3.0).sqrt()).abs() < 1e-12);
+}
+
+#[test]
+fn test_done() {
+    let adaptive = AdaptiveSampling::new(4, 16, 0.01);
+    let mut flat = PixelEstimate::default();
+    while !adaptive.done(&flat) {
+        flat.add(Radiance::new(0.5, 0.5, 0.5));
+    }
+    assert_eq!(flat.count(), 4);
+
+    let mut noisy = PixelEstimate::default();
+    let mut bright = false;
+    while !adaptive.done(&noisy) {
+        bright = !bright;
+        noisy.add(Radiance::new(bright as u8 as f64, 0.0, 0.0));
+    }
+    assert_eq!(noisy.count(), 16);
+}
+
+#[test]
+fn test_heat() {
+    assert_eq!(heat(0, 8), Radiance::new(0.0, 0.0, 1.0));
+    assert_eq!(heat(4, 8), Radiance::new(0.0, 1.0, 0.0));
+    assert_eq!(heat(8, 8), Radiance::new(1.0, 0.0, 0.0));
+}
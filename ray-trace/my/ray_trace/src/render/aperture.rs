@@ -0,0 +1,258 @@
+use std::f64::consts::{FRAC_PI_2, FRAC_PI_4, PI};
+use std::path::Path;
+use std::sync::Arc;
+
+// the shape of the lens opening, out of focus highlights take this shape.
+// Every shape fits the unit disk, the camera scales it by the aperture radius
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum ApertureShape {
+    #[default]
+    Circle,
+    // a regular polygon with `blades` corners, `rotation` in degrees
+    Polygon {
+        blades: u32,
+        rotation: f64,
+    },
+    // a ring, `inner` is the radius of the hole, like a mirror lens
+    Annulus {
+        inner: f64,
+    },
+    // any picture, brighter pixels let more light through
+    Mask(Arc<ApertureMask>),
+}
+
+impl ApertureShape {
+    // maps a point of the unit square onto the opening, uniformly by area
+    // (by brightness for a mask)
+    pub fn sample(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        match self {
+            Self::Circle => concentric_disk((x, y)),
+            Self::Polygon { blades, rotation } => {
+                // pick one of the triangles between the center and an edge
+                let blades = (*blades).max(3) as f64;
+                let sector = (x * blades).floor().min(blades - 1.0);
+                let t = x * blades - sector;
+                let corner = |i: f64| {
+                    let angle = rotation.to_radians() + FRAC_PI_2 + 2.0 * PI * i / blades;
+                    (angle.cos(), angle.sin())
+                };
+                let (a, b) = (corner(sector), corner(sector + 1.0));
+                let r = y.sqrt();
+                (
+                    r * ((1.0 - t) * a.0 + t * b.0),
+                    r * ((1.0 - t) * a.1 + t * b.1),
+                )
+            }
+            Self::Annulus { inner } => {
+                let inner = inner.clamp(0.0, 1.0);
+                let r = (inner * inner + (1.0 - inner * inner) * x).sqrt();
+                let theta = 2.0 * PI * y;
+                (r * theta.cos(), r * theta.sin())
+            }
+            Self::Mask(mask) => mask.sample((x, y)),
+        }
+    }
+}
+
+// Shirley and Chiu's mapping of the unit square onto the unit disk, keeps
+// stratified samples stratified
+pub fn concentric_disk((x, y): (f64, f64)) -> (f64, f64) {
+    let (x, y) = (2.0 * x - 1.0, 2.0 * y - 1.0);
+    if x == 0.0 && y == 0.0 {
+        return (0.0, 0.0);
+    }
+    let (r, theta) = if x.abs() > y.abs() {
+        (x, FRAC_PI_4 * (y / x))
+    } else {
+        (y, FRAC_PI_2 - FRAC_PI_4 * (x / y))
+    };
+    (r * theta.cos(), r * theta.sin())
+}
+
+////////// mask //////////
+// a greyscale picture stretched over [-1, 1]^2, sampled by picking a row by
+// its total brightness, then a pixel in the row
+#[derive(Debug, PartialEq)]
+pub struct ApertureMask {
+    width: usize,
+    height: usize,
+    rows: Vec<f64>,    // cumulative, `height + 1` entries from 0 to 1
+    columns: Vec<f64>, // cumulative per row, `width + 1` entries each
+}
+
+impl ApertureMask {
+    // `weights` row-major from the top, anything non negative
+    pub fn new(width: usize, height: usize, weights: &[f64]) -> std::io::Result<Self> {
+        if width == 0 || height == 0 || weights.len() != width * height {
+            return Err(invalid("mask size does not match its pixels"));
+        }
+
+        let mut rows = vec![0.0];
+        let mut columns = Vec::with_capacity(height * (width + 1));
+        for row in weights.chunks(width) {
+            let start = columns.len();
+            columns.push(0.0);
+            for weight in row {
+                columns.push(columns.last().unwrap() + weight.max(0.0));
+            }
+            let total = columns[start + width];
+            for (i, value) in columns[start..].iter_mut().enumerate() {
+                // a black row is never picked, keep it uniform anyway
+                *value = if total > 0.0 {
+                    *value / total
+                } else {
+                    i as f64 / width as f64
+                };
+            }
+            rows.push(rows.last().unwrap() + total);
+        }
+        let total = *rows.last().unwrap();
+        if total <= 0.0 {
+            return Err(invalid("mask lets no light through"));
+        }
+        for value in &mut rows {
+            *value /= total;
+        }
+
+        Ok(Self {
+            width,
+            height,
+            rows,
+            columns,
+        })
+    }
+
+    // from a png, by brightness
+    pub fn load<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        let mut decoder = ::png::Decoder::new(std::fs::File::open(path)?);
+        decoder
+            .set_transformations(::png::Transformations::EXPAND | ::png::Transformations::STRIP_16);
+        let mut reader = decoder.read_info().map_err(std::io::Error::other)?;
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let info = reader
+            .next_frame(&mut buffer)
+            .map_err(std::io::Error::other)?;
+
+        let channels = info.color_type.samples();
+        let weights: Vec<f64> = buffer[..info.buffer_size()]
+            .chunks(channels)
+            .map(|pixel| match pixel.len() {
+                // grey, grey and alpha
+                1 | 2 => pixel[0] as f64 / 255.0,
+                _ => (pixel[0] as f64 + pixel[1] as f64 + pixel[2] as f64) / (3.0 * 255.0),
+            })
+            .collect();
+        Self::new(info.width as usize, info.height as usize, &weights)
+    }
+
+    fn sample(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        // the index where `cdf` passes `value`, and how far into that cell
+        let find = |cdf: &[f64], value: f64| {
+            let cells = cdf.len() - 1;
+            let index = cdf.partition_point(|&c| c <= value).clamp(1, cells) - 1;
+            let width = cdf[index + 1] - cdf[index];
+            let offset = if width > 0.0 {
+                ((value - cdf[index]) / width).clamp(0.0, 1.0)
+            } else {
+                0.5
+            };
+            (index, offset)
+        };
+
+        let (row, dy) = find(&self.rows, y);
+        let start = row * (self.width + 1);
+        let (col, dx) = find(&self.columns[start..start + self.width + 1], x);
+        (
+            2.0 * (col as f64 + dx) / self.width as f64 - 1.0,
+            1.0 - 2.0 * (row as f64 + dy) / self.height as f64,
+        )
+    }
+}
+
+fn invalid(message: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidInput, message)
+}
+
+////////// UT //////////
+#[cfg(test)]
+fn grid(n: usize) -> impl Iterator<Item = (f64, f64)> {
+    (0..n * n).map(move |i| {
+        (
+            ((i % n) as f64 + 0.5) / n as f64,
+            ((i / n) as f64 + 0.5) / n as f64,
+        )
+    })
+}
+
+#[test]
+fn test_concentric_disk() {
+    assert_eq!(concentric_disk((0.5, 0.5)), (0.0, 0.0));
+    let (x, y) = concentric_disk((1.0, 0.5));
+    assert!((x - 1.0).abs() < 1e-12 && y.abs() < 1e-12);
+    for (x, y) in grid(10).map(concentric_disk) {
+        assert!(x * x + y * y <= 1.0 + 1e-12);
+    }
+}
+
+#[test]
+fn test_polygon() {
+    let blades = 6;
+    let shape = ApertureShape::Polygon {
+        blades,
+        rotation: 0.0,
+    };
+    // inside every edge: the distance to the center along each edge normal
+    // is at most the apothem
+    let apothem = (PI / blades as f64).cos();
+    for (x, y) in grid(20).map(|p| shape.sample(p)) {
+        for i in 0..blades {
+            let angle = FRAC_PI_2 + (2.0 * i as f64 + 1.0) * PI / blades as f64;
+            assert!(x * angle.cos() + y * angle.sin() <= apothem + 1e-12);
+        }
+    }
+    // a corner points up without rotation
+    let (x, y) = shape.sample((0.0, 1.0));
+    assert!(x.abs() < 1e-12 && (y - 1.0).abs() < 1e-12);
+}
+
+#[test]
+fn test_annulus() {
+    let shape = ApertureShape::Annulus { inner: 0.6 };
+    for (x, y) in grid(10).map(|p| shape.sample(p)) {
+        let r = (x * x + y * y).sqrt();
+        assert!((0.6..=1.0 + 1e-12).contains(&r));
+    }
+}
+
+#[test]
+fn test_mask() {
+    // only the top right pixel of a 4x2 mask is lit
+    let mut weights = vec![0.0; 8];
+    weights[3] = 1.0;
+    let mask = ApertureMask::new(4, 2, &weights).unwrap();
+    for (x, y) in grid(10).map(|p| mask.sample(p)) {
+        assert!(
+            (0.5..=1.0).contains(&x) && (0.0..=1.0).contains(&y),
+            "{} {}",
+            x,
+            y
+        );
+    }
+    assert!(ApertureMask::new(4, 2, &[0.0; 8]).is_err());
+    assert!(ApertureMask::new(4, 2, &[1.0; 3]).is_err());
+}
+
+#[test]
+fn test_mask_from_png() {
+    let path = std::env::temp_dir().join(format!("ray_trace_test_{}_mask.png", std::process::id()));
+    let mut encoder = ::png::Encoder::new(std::fs::File::create(&path).unwrap(), 2, 1);
+    encoder.set_color(::png::ColorType::Grayscale);
+    encoder.set_depth(::png::BitDepth::Eight);
+    let mut writer = encoder.write_header().unwrap();
+    writer.write_image_data(&[0, 255]).unwrap();
+    writer.finish().unwrap();
+
+    let mask = ApertureMask::load(&path).unwrap();
+    std::fs::remove_file(path).unwrap();
+    assert_eq!(mask, ApertureMask::new(2, 1, &[0.0, 1.0]).unwrap());
+}
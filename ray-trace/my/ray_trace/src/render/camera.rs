@@ -1,9 +1,100 @@
 use crate::common::ray::Ray;
 use crate::common::vec3::{Point3, Vec3};
 use crate::geometry::list::GeometryList;
-use crate::geometry::world::World;
+use crate::render::aperture::ApertureShape;
 use crate::render::take_photo_settings::TakePhotoSettings;
 
+// how wide the camera sees, in degrees, across the picture height, width or
+// diagonal
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FieldOfView {
+    Vertical(f64),
+    Horizontal(f64),
+    Diagonal(f64),
+}
+
+impl Default for FieldOfView {
+    fn default() -> Self {
+        // the old fixed viewport, 2.0 high at distance 1
+        Self::Vertical(90.0)
+    }
+}
+
+impl FieldOfView {
+    // height of the viewport one unit in front of the camera
+    fn viewport_height(&self, aspect_ratio: f64) -> f64 {
+        let extent = |degrees: f64| 2.0 * (degrees.to_radians() / 2.0).tan();
+        match *self {
+            Self::Vertical(degrees) => extent(degrees),
+            Self::Horizontal(degrees) => extent(degrees) / aspect_ratio,
+            Self::Diagonal(degrees) => extent(degrees) / (1.0 + aspect_ratio * aspect_ratio).sqrt(),
+        }
+    }
+}
+
+// how the scene is flattened onto the picture
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Projection {
+    // rays from one point, far things look smaller
+    #[default]
+    Perspective,
+    // parallel rays along the view, `height` world units top to bottom, sizes
+    // don't change with distance
+    Orthographic {
+        height: f64,
+    },
+    // parallel rays slanted so depth shows as a shift by `depth_scale` times
+    // the distance, towards `angle` degrees counterclockwise from the right
+    Oblique {
+        height: f64,
+        angle: f64,
+        depth_scale: f64,
+    },
+}
+
+impl Projection {
+    // depth drawn at half scale at 45 degrees
+    pub const fn cabinet(height: f64) -> Self {
+        Self::Oblique {
+            height,
+            angle: 45.0,
+            depth_scale: 0.5,
+        }
+    }
+
+    // depth drawn at full scale at 45 degrees
+    pub const fn cavalier(height: f64) -> Self {
+        Self::Oblique {
+            height,
+            angle: 45.0,
+            depth_scale: 1.0,
+        }
+    }
+}
+
+// where a camera ray starts, all in [0, 1): `u`, `v` on the picture from the
+// left bottom, `lens` on the square the lens disk is mapped from, `time`
+// through the shutter interval
+#[derive(Debug, Clone, PartialEq)]
+pub struct CameraSample {
+    pub u: f64,
+    pub v: f64,
+    pub lens: (f64, f64),
+    pub time: f64,
+}
+
+impl CameraSample {
+    // through the center of the lens, halfway through the exposure
+    pub const fn new(u: f64, v: f64) -> Self {
+        Self {
+            u,
+            v,
+            lens: (0.5, 0.5),
+            time: 0.5,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Camera {
     origin: Point3,
@@ -12,64 +103,119 @@ pub struct Camera {
     vertical_full: Vec3,
     horizontal_unit: Vec3,
     vertical_unit: Vec3,
+    parallel_direction: Option<Vec3>, // the same for every ray unless perspective
     pub(crate) aspect_ratio: f64,
-    // aperture: f64,
+    lens_radius: f64,
+    aperture_shape: ApertureShape,
     shutter_speed: f64,
 }
 
 impl Camera {
-    pub fn new(
-        look_from: &Point3,
-        look_at: &Point3,
-        // vup: &Vec3,
-        // fov: f64,
-        aspect_ratio: f64,
-        // aperture: f64,
-        focus_distance: f64,
-        // shutter_speed: f64,
-    ) -> Self {
-        // let fov = d2r(fov);
-        // let h = (fov / 2.0).tan();
-        // let vh = 2.0 * h;
-        let vh = 2.0;
-        let vw = vh * aspect_ratio;
-
-        let w = (look_at - look_from).unit();
-        // let horizontal_unit = w.cross(Vec3(0.0,1.0,0.0))
-        let horizontal_unit = w.cross(&Vec3::new(0.0, 1.0, 0.0)).unit();
+    // see `CameraBuilder` for what every setting does
+    pub fn new(builder: CameraBuilder) -> Self {
+        let CameraBuilder {
+            look_from,
+            look_at,
+            vup,
+            fov,
+            aspect_ratio,
+            aperture,
+            aperture_shape,
+            focus_distance,
+            auto_focus,
+            shutter_speed,
+            projection,
+        } = builder;
+        let focus_distance = if auto_focus {
+            (&look_at - &look_from).length()
+        } else {
+            focus_distance
+        };
+
+        let w = (&look_at - &look_from).unit();
+        let horizontal_unit = Self::horizontal_unit(&w, &vup);
         let vertical_unit = horizontal_unit.cross(&w).unit();
 
-        let horizontal_full = focus_distance * vw * &horizontal_unit;
-        let vertical_full = focus_distance * vh * &vertical_unit;
-        let left_bottom = look_from - &horizontal_full / 2.0 - &vertical_full / 2.0 +
-            //focus_distance *   hongfendong
-            w;
+        let (horizontal_full, vertical_full, left_bottom, parallel_direction) = match projection {
+            Projection::Perspective => {
+                let vh = fov.viewport_height(aspect_ratio);
+                let vw = vh * aspect_ratio;
+                let horizontal_full = focus_distance * vw * &horizontal_unit;
+                let vertical_full = focus_distance * vh * &vertical_unit;
+                // the viewport is on the focus plane, where lens rays converge
+                let left_bottom = &look_from - &horizontal_full / 2.0 - &vertical_full / 2.0
+                    + focus_distance * &w;
+                (horizontal_full, vertical_full, left_bottom, None)
+            }
+            Projection::Orthographic { height } | Projection::Oblique { height, .. } => {
+                // the viewport is where the rays start, around `look_from`
+                let horizontal_full = height * aspect_ratio * &horizontal_unit;
+                let vertical_full = height * &vertical_unit;
+                let left_bottom = &look_from - &horizontal_full / 2.0 - &vertical_full / 2.0;
+                let direction = match projection {
+                    Projection::Oblique {
+                        angle, depth_scale, ..
+                    } => {
+                        // going deeper moves the ray against the shift, so
+                        // deeper points show up shifted towards it
+                        let angle = angle.to_radians();
+                        let shift = angle.cos() * &horizontal_unit + angle.sin() * &vertical_unit;
+                        &w - depth_scale * shift
+                    }
+                    _ => w.clone(),
+                };
+                (horizontal_full, vertical_full, left_bottom, Some(direction))
+            }
+        };
         Self {
-            origin: look_from.clone(),
-            left_bottom: left_bottom,
+            origin: look_from,
+            left_bottom,
             horizontal_full,
             vertical_full,
             horizontal_unit,
             vertical_unit,
+            parallel_direction,
             aspect_ratio,
-            shutter_speed: 0.0,
+            lens_radius: aperture,
+            aperture_shape,
+            shutter_speed,
+        }
+    }
+
+    // right of the picture, perpendicular to the view and to `vup`. When
+    // looking along `vup` any perpendicular does, take the axis the view is
+    // least aligned with as up
+    fn horizontal_unit(w: &Vec3, vup: &Vec3) -> Vec3 {
+        let right = w.cross(vup);
+        if right.length_squared() > 1e-12 * vup.length_squared() {
+            return right.unit();
         }
+        let (x, y, z) = (w.x.abs(), w.y.abs(), w.z.abs());
+        let up = if x <= y && x <= z {
+            Vec3::new(1.0, 0.0, 0.0)
+        } else if y <= z {
+            Vec3::new(0.0, 1.0, 0.0)
+        } else {
+            Vec3::new(0.0, 0.0, 1.0)
+        };
+        w.cross(&up).unit()
     }
 
-    pub fn ray(&self, u: f64, v: f64) -> Ray {
-        let rd = Vec3::default(); // hongfendong
+    pub fn ray(&self, sample: &CameraSample) -> Ray {
+        let (u, v) = (sample.u, sample.v);
+        // parallel projections have no lens to focus, the aperture is ignored
+        if let Some(direction) = &self.parallel_direction {
+            let origin = &self.left_bottom + u * &self.horizontal_full + v * &self.vertical_full;
+            return Ray::new(origin, direction.clone()).time(sample.time * self.shutter_speed);
+        }
+
+        let (x, y) = self.aperture_shape.sample(sample.lens);
+        let rd = Vec3::new(x, y, 0.0) * self.lens_radius;
         let offset = &self.horizontal_unit * rd.x + &self.vertical_unit * rd.y;
         let origin = &self.origin + offset;
         let direction =
             &self.left_bottom + u * &self.horizontal_full + v * &self.vertical_full - &origin;
-        // dbg!(
-        //     &direction,
-        //     &self.left_bottom,
-        //     u * &self.horizontal_full,
-        //     v * &self.vertical_full,
-        //     &origin
-        // );
-        Ray::new(origin, direction)
+        Ray::new(origin, direction).time(sample.time * self.shutter_speed)
     }
 
     pub fn take_photo(&self, world: GeometryList) -> TakePhotoSettings<'_> {
@@ -81,12 +227,15 @@ impl Camera {
 pub struct CameraBuilder {
     look_from: Point3,
     look_at: Point3,
-    // vup:Vec3,
-    // fov :f64,
+    vup: Vec3,
+    fov: FieldOfView,
     aspect_ratio: f64,
-    // aperture:f64,
+    aperture: f64,
+    aperture_shape: ApertureShape,
     focus_distance: f64,
-    // shutter_spped:f64,
+    auto_focus: bool,
+    shutter_speed: f64,
+    projection: Projection,
 }
 
 impl Default for CameraBuilder {
@@ -94,12 +243,15 @@ impl Default for CameraBuilder {
         Self {
             look_from: Point3::default(),
             look_at: Point3::new(0.0, 0.0, -1.0),
-            //     vup
-            // fov
+            vup: Vec3::new(0.0, 1.0, 0.0),
+            fov: FieldOfView::default(),
             aspect_ratio: 16.0 / 9.0,
-            //     aperture
+            aperture: 0.0,
+            aperture_shape: ApertureShape::Circle,
             focus_distance: 1.0,
-            // shutter_speed
+            auto_focus: false,
+            shutter_speed: 0.0,
+            projection: Projection::Perspective,
         }
     }
 }
@@ -115,26 +267,214 @@ impl CameraBuilder {
         self
     }
 
+    // tilting it rolls the camera
+    pub const fn vup(mut self, vup: Vec3) -> Self {
+        self.vup = vup;
+        self
+    }
+
+    pub const fn fov(mut self, fov: FieldOfView) -> Self {
+        self.fov = fov;
+        self
+    }
+
     pub const fn aspect_ratio(mut self, aspect_ratio: f64) -> Self {
         self.aspect_ratio = aspect_ratio;
         self
     }
 
+    // radius of the lens, the wider the blurrier everything off the focus plane
+    pub const fn aperture(mut self, aperture: f64) -> Self {
+        self.aperture = aperture;
+        self
+    }
+
+    // the shape out of focus highlights take
+    pub fn aperture_shape(mut self, aperture_shape: ApertureShape) -> Self {
+        self.aperture_shape = aperture_shape;
+        self
+    }
+
     pub const fn focus_distance(mut self, focus_distance: f64) -> Self {
         self.focus_distance = focus_distance;
         self
     }
 
+    // focus on `look_at`, ignores `focus_distance`
+    pub const fn auto_focus(mut self, auto_focus: bool) -> Self {
+        self.auto_focus = auto_focus;
+        self
+    }
+
+    // how long the shutter stays open, moving objects blur over it
+    pub const fn shutter_speed(mut self, shutter_speed: f64) -> Self {
+        self.shutter_speed = shutter_speed;
+        self
+    }
+
+    // `fov`, `aperture` and focus only matter for perspective
+    pub const fn projection(mut self, projection: Projection) -> Self {
+        self.projection = projection;
+        self
+    }
+
     pub fn build(self) -> Camera {
-        Camera::new(
-            &self.look_from,
-            &self.look_at,
-            // &self.vup,
-            // self.fov,
-            self.aspect_ratio,
-            // self.aperture,
-            self.focus_distance,
-            // self.shutter_speed,
-        )
+        Camera::new(self)
+    }
+}
+
+////////// UT //////////
+#[cfg(test)]
+fn angle(a: &Vec3, b: &Vec3) -> f64 {
+    (a.dot(b) / a.length() / b.length()).acos().to_degrees()
+}
+
+#[test]
+fn test_fov() {
+    let forward = Vec3::new(0.0, 0.0, -1.0);
+    let camera = CameraBuilder::default().aspect_ratio(2.0).build();
+    assert!(
+        (angle(
+            &camera.ray(&CameraSample::new(0.5, 1.0)).direction,
+            &forward
+        ) - 45.0)
+            .abs()
+            < 1e-9
+    );
+
+    let camera = CameraBuilder::default()
+        .aspect_ratio(2.0)
+        .fov(FieldOfView::Vertical(40.0))
+        .build();
+    assert!(
+        (angle(
+            &camera.ray(&CameraSample::new(0.5, 0.0)).direction,
+            &forward
+        ) - 20.0)
+            .abs()
+            < 1e-9
+    );
+
+    let camera = CameraBuilder::default()
+        .aspect_ratio(2.0)
+        .fov(FieldOfView::Horizontal(60.0))
+        .build();
+    assert!(
+        (angle(
+            &camera.ray(&CameraSample::new(1.0, 0.5)).direction,
+            &forward
+        ) - 30.0)
+            .abs()
+            < 1e-9
+    );
+
+    let camera = CameraBuilder::default()
+        .aspect_ratio(2.0)
+        .fov(FieldOfView::Diagonal(80.0))
+        .build();
+    assert!(
+        (angle(
+            &camera.ray(&CameraSample::new(1.0, 1.0)).direction,
+            &forward
+        ) - 40.0)
+            .abs()
+            < 1e-9
+    );
+}
+
+#[test]
+fn test_vup_rolls() {
+    let camera = CameraBuilder::default()
+        .vup(Vec3::new(1.0, 0.0, 0.0))
+        .build();
+    let top = camera.ray(&CameraSample::new(0.5, 1.0)).direction;
+    assert!(top.x > 0.0 && top.y.abs() < 1e-9);
+}
+
+#[test]
+fn test_look_along_vup() {
+    for look_at in [Point3::new(0.0, -1.0, 0.0), Point3::new(0.0, 5.0, 0.0)] {
+        let camera = CameraBuilder::default().look_at(look_at.clone()).build();
+        let center = camera.ray(&CameraSample::new(0.5, 0.5)).direction;
+        assert!(angle(&center, &look_at) < 1e-6);
+        let corner = camera.ray(&CameraSample::new(0.0, 0.0)).direction;
+        assert!(corner.x.is_finite() && corner.y.is_finite() && corner.z.is_finite());
+    }
+}
+
+#[test]
+fn test_thin_lens_focus_plane() {
+    let focus_on = |camera: &Camera, lens: (f64, f64)| {
+        let ray = camera.ray(&CameraSample {
+            lens,
+            ..CameraSample::new(0.3, 0.8)
+        });
+        // where the ray crosses z = -4
+        let t = (-4.0 - ray.origin.z) / ray.direction.z;
+        ray.at(t)
+    };
+
+    let camera = CameraBuilder::default()
+        .look_at(Point3::new(0.0, 0.0, -4.0))
+        .aperture(0.5)
+        .auto_focus(true)
+        .build();
+    // rays through any point of the lens meet on the focus plane
+    let center = focus_on(&camera, (0.5, 0.5));
+    for lens in [(0.0, 0.0), (0.9, 0.2), (0.3, 0.7)] {
+        assert!((focus_on(&camera, lens) - &center).length() < 1e-9);
     }
+    let ray = camera.ray(&CameraSample {
+        lens: (0.0, 0.5),
+        ..CameraSample::new(0.3, 0.8)
+    });
+    assert!((ray.origin.x + 0.5).abs() < 1e-9);
+
+    // and spread anywhere else
+    let camera = CameraBuilder::default()
+        .look_at(Point3::new(0.0, 0.0, -4.0))
+        .aperture(0.5)
+        .focus_distance(2.0)
+        .build();
+    let center = focus_on(&camera, (0.5, 0.5));
+    assert!((focus_on(&camera, (0.0, 0.0)) - &center).length() > 0.1);
+}
+
+#[test]
+fn test_shutter_time() {
+    let camera = CameraBuilder::default().shutter_speed(0.25).build();
+    let ray = camera.ray(&CameraSample {
+        time: 0.5,
+        ..CameraSample::new(0.5, 0.5)
+    });
+    assert_eq!(ray.time, 0.125);
+}
+
+#[test]
+fn test_orthographic() {
+    let camera = CameraBuilder::default()
+        .aspect_ratio(2.0)
+        .projection(Projection::Orthographic { height: 3.0 })
+        .build();
+    let left = camera.ray(&CameraSample::new(0.0, 0.5));
+    let right = camera.ray(&CameraSample::new(1.0, 0.5));
+    let top = camera.ray(&CameraSample::new(0.5, 1.0));
+    // parallel rays, the picture is 6 x 3 world units
+    assert_eq!(left.direction, Vec3::new(0.0, 0.0, -1.0));
+    assert_eq!(left.direction, right.direction);
+    assert_eq!((&right.origin - &left.origin).length(), 6.0);
+    assert_eq!(top.origin, Point3::new(0.0, 1.5, 0.0));
+}
+
+#[test]
+fn test_cabinet() {
+    let camera = CameraBuilder::default()
+        .projection(Projection::cabinet(2.0))
+        .build();
+    // a point 2 deep straight ahead shows up half of that towards the top right
+    let ray = camera.ray(&CameraSample::new(0.5, 0.5));
+    let t = -2.0 / ray.direction.z;
+    let point = ray.at(t);
+    let shift = 0.5 * 2.0 * std::f64::consts::FRAC_1_SQRT_2;
+    assert!((point.x + shift).abs() < 1e-12 && (point.y + shift).abs() < 1e-12);
 }
@@ -0,0 +1,431 @@
+use crate::common::vec3::Radiance;
+use crate::render::output_format::RowWriter;
+use std::io::{BufWriter, Write};
+
+#[cfg(test)]
+use std::convert::TryInto;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ExrPrecision {
+    // 16 bit floats, what most compositors expect
+    #[default]
+    Half,
+    Float,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ExrCompression {
+    None,
+    // zlib over blocks of 16 scanlines
+    #[default]
+    Zip,
+}
+
+impl ExrCompression {
+    const fn lines_per_block(&self) -> usize {
+        match self {
+            Self::None => 1,
+            Self::Zip => 16,
+        }
+    }
+
+    const fn id(&self) -> u8 {
+        match self {
+            Self::None => 0,
+            Self::Zip => 3,
+        }
+    }
+}
+
+// single part scanline OpenEXR with R, G, B channels of linear radiance.
+// The offset table comes before the pixels, so everything is kept until `finish`
+pub(crate) struct ExrWriter {
+    file: BufWriter<Box<dyn Write>>,
+    width: usize,
+    height: usize,
+    precision: ExrPrecision,
+    compression: ExrCompression,
+    rows: Vec<Vec<Radiance>>,
+}
+
+impl ExrWriter {
+    pub fn new(
+        file: BufWriter<Box<dyn Write>>,
+        width: usize,
+        height: usize,
+        precision: ExrPrecision,
+        compression: ExrCompression,
+    ) -> Self {
+        Self {
+            file,
+            width,
+            height,
+            precision,
+            compression,
+            rows: Vec::with_capacity(height),
+        }
+    }
+
+    fn header(&self) -> Vec<u8> {
+        fn attribute(header: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+            header.extend_from_slice(name.as_bytes());
+            header.push(0);
+            header.extend_from_slice(kind.as_bytes());
+            header.push(0);
+            header.extend_from_slice(&(value.len() as i32).to_le_bytes());
+            header.extend_from_slice(value);
+        }
+
+        let mut header = vec![];
+        // magic number, then version 2 with no flags: single part scanline
+        header.extend_from_slice(&[0x76, 0x2f, 0x31, 0x01, 2, 0, 0, 0]);
+
+        let pixel_type: i32 = match self.precision {
+            ExrPrecision::Half => 1,
+            ExrPrecision::Float => 2,
+        };
+        let mut channels = vec![];
+        // channels are sorted by name
+        for name in ["B", "G", "R"] {
+            channels.extend_from_slice(name.as_bytes());
+            channels.push(0);
+            channels.extend_from_slice(&pixel_type.to_le_bytes());
+            channels.extend_from_slice(&[0, 0, 0, 0]); // pLinear, reserved
+            channels.extend_from_slice(&1i32.to_le_bytes()); // x sampling
+            channels.extend_from_slice(&1i32.to_le_bytes()); // y sampling
+        }
+        channels.push(0);
+        attribute(&mut header, "channels", "chlist", &channels);
+        attribute(
+            &mut header,
+            "compression",
+            "compression",
+            &[self.compression.id()],
+        );
+
+        let window: Vec<u8> = [0, 0, self.width as i32 - 1, self.height as i32 - 1]
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect();
+        attribute(&mut header, "dataWindow", "box2i", &window);
+        attribute(&mut header, "displayWindow", "box2i", &window);
+        attribute(&mut header, "lineOrder", "lineOrder", &[0]); // increasing y
+        attribute(
+            &mut header,
+            "pixelAspectRatio",
+            "float",
+            &1f32.to_le_bytes(),
+        );
+        let center: Vec<u8> = [0f32, 0f32].iter().flat_map(|v| v.to_le_bytes()).collect();
+        attribute(&mut header, "screenWindowCenter", "v2f", &center);
+        attribute(
+            &mut header,
+            "screenWindowWidth",
+            "float",
+            &1f32.to_le_bytes(),
+        );
+        header.push(0);
+        header
+    }
+
+    // every scanline holds all of B, then all of G, then all of R
+    fn block_data(&self, rows: &[Vec<Radiance>]) -> Vec<u8> {
+        let mut data = vec![];
+        for row in rows {
+            for channel in [2, 1, 0] {
+                for pixel in row {
+                    match self.precision {
+                        ExrPrecision::Half => data
+                            .extend_from_slice(&f32_to_half(pixel[channel] as f32).to_le_bytes()),
+                        ExrPrecision::Float => {
+                            data.extend_from_slice(&(pixel[channel] as f32).to_le_bytes())
+                        }
+                    }
+                }
+            }
+        }
+
+        match self.compression {
+            ExrCompression::None => data,
+            ExrCompression::Zip => {
+                let compressed = miniz_oxide::deflate::compress_to_vec_zlib(&zip_predict(&data), 6);
+                // readers take a block as raw when it didn't get smaller
+                if compressed.len() < data.len() {
+                    compressed
+                } else {
+                    data
+                }
+            }
+        }
+    }
+}
+
+impl RowWriter for ExrWriter {
+    fn write_row(&mut self, _row: usize, pixels: &[Radiance]) -> std::io::Result<()> {
+        self.rows.push(pixels.to_vec());
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> std::io::Result<()> {
+        let header = self.header();
+        let blocks: Vec<Vec<u8>> = self
+            .rows
+            .chunks(self.compression.lines_per_block())
+            .map(|rows| self.block_data(rows))
+            .collect();
+
+        // absolute position of every block, each one starts with its y and size
+        let mut offset = (header.len() + blocks.len() * 8) as u64;
+        let mut offsets = Vec::with_capacity(blocks.len() * 8);
+        for block in &blocks {
+            offsets.extend_from_slice(&offset.to_le_bytes());
+            offset += 8 + block.len() as u64;
+        }
+
+        self.file.write_all(&header)?;
+        self.file.write_all(&offsets)?;
+        for (index, block) in blocks.iter().enumerate() {
+            let y = (index * self.compression.lines_per_block()) as i32;
+            self.file.write_all(&y.to_le_bytes())?;
+            self.file.write_all(&(block.len() as i32).to_le_bytes())?;
+            self.file.write_all(block)?;
+        }
+        self.file.flush()
+    }
+}
+
+// the byte shuffle and delta predictor OpenEXR runs before zlib
+fn zip_predict(data: &[u8]) -> Vec<u8> {
+    let half = data.len().div_ceil(2);
+    let mut shuffled = vec![0; data.len()];
+    for (index, &byte) in data.iter().enumerate() {
+        let target = if index % 2 == 0 {
+            index / 2
+        } else {
+            half + index / 2
+        };
+        shuffled[target] = byte;
+    }
+
+    let mut previous = shuffled.first().copied().unwrap_or_default();
+    for byte in shuffled.iter_mut().skip(1) {
+        let current = *byte;
+        *byte = current.wrapping_sub(previous).wrapping_add(128);
+        previous = current;
+    }
+    shuffled
+}
+
+// IEEE 754 binary16, rounded to nearest even
+fn f32_to_half(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7f_ffff;
+
+    if exponent == 0xff {
+        // infinity stays infinity, nan stays nan
+        return sign | 0x7c00 | if mantissa != 0 { 0x200 } else { 0 };
+    }
+
+    let exponent = exponent - 127 + 15;
+    if exponent >= 0x1f {
+        return sign | 0x7c00;
+    }
+
+    let round = |value: u32, shift: u32| {
+        let kept = value >> shift;
+        let rest = value & ((1 << shift) - 1);
+        let halfway = 1 << (shift - 1);
+        if rest > halfway || (rest == halfway && kept & 1 == 1) {
+            kept + 1
+        } else {
+            kept
+        }
+    };
+
+    if exponent <= 0 {
+        // subnormal, too small ones flush to zero
+        if exponent < -10 {
+            return sign;
+        }
+        let mantissa = mantissa | 0x80_0000;
+        return sign | round(mantissa, (14 - exponent) as u32) as u16;
+    }
+
+    // a carry out of the mantissa bumps the exponent, which is still right
+    sign | round(((exponent as u32) << 23) | mantissa, 13) as u16
+}
+
+////////// UT //////////
+#[test]
+fn test_f32_to_half() {
+    assert_eq!(f32_to_half(0.0), 0x0000);
+    assert_eq!(f32_to_half(-0.0), 0x8000);
+    assert_eq!(f32_to_half(1.0), 0x3c00);
+    assert_eq!(f32_to_half(0.5), 0x3800);
+    assert_eq!(f32_to_half(-2.0), 0xc000);
+    assert_eq!(f32_to_half(65504.0), 0x7bff);
+    assert_eq!(f32_to_half(1e6), 0x7c00);
+    assert_eq!(f32_to_half(f32::INFINITY), 0x7c00);
+    assert_eq!(f32_to_half(2f32.powi(-24)), 0x0001);
+    assert_eq!(f32_to_half(2f32.powi(-14)), 0x0400);
+    // 1 + 2^-11 is halfway between 1 and the next half, ties to even
+    assert_eq!(f32_to_half(1.0 + 2f32.powi(-11)), 0x3c00);
+    assert_eq!(f32_to_half(1.0 + 3.0 * 2f32.powi(-11)), 0x3c02);
+}
+
+#[test]
+fn test_zip_predict() {
+    assert_eq!(zip_predict(&[1, 10, 2, 20, 3]), vec![1, 129, 129, 135, 138]);
+}
+
+#[test]
+fn test_write() {
+    for compression in [ExrCompression::None, ExrCompression::Zip] {
+        let path = std::env::temp_dir().join(format!(
+            "ray_trace_test_{}_{:?}.exr",
+            std::process::id(),
+            compression
+        ));
+        let out: Box<dyn Write> = Box::new(std::fs::File::create(&path).unwrap());
+        let mut writer = Box::new(ExrWriter::new(
+            BufWriter::new(out),
+            3,
+            20,
+            ExrPrecision::Float,
+            compression,
+        ));
+        for row in 0..20 {
+            writer
+                .write_row(row, &vec![Radiance::new(row as f64, 0.5, 4.0); 3])
+                .unwrap();
+        }
+        writer.finish().unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(&bytes[..4], &[0x76, 0x2f, 0x31, 0x01]);
+        let header_end = writer_header_len(&bytes);
+        let blocks = 20usize.div_ceil(compression.lines_per_block());
+        let first_offset =
+            u64::from_le_bytes(bytes[header_end..header_end + 8].try_into().unwrap()) as usize;
+        assert_eq!(first_offset, header_end + blocks * 8);
+        // first block starts at scanline 0
+        assert_eq!(&bytes[first_offset..first_offset + 4], &0i32.to_le_bytes());
+    }
+}
+
+#[cfg(test)]
+fn writer_header_len(bytes: &[u8]) -> usize {
+    // attributes are "name\0type\0size value", the header ends with an empty name
+    let mut index = 8;
+    while bytes[index] != 0 {
+        while bytes[index] != 0 {
+            index += 1;
+        }
+        index += 1;
+        while bytes[index] != 0 {
+            index += 1;
+        }
+        index += 1;
+        let size = i32::from_le_bytes(bytes[index..index + 4].try_into().unwrap()) as usize;
+        index += 4 + size;
+    }
+    index + 1
+}
+
+#[test]
+fn test_round_trip() {
+    // three zip blocks, the last one short
+    let (width, height) = (5, 37);
+    let pixel = |row: usize, col: usize| {
+        Radiance::new(
+            row as f64 + col as f64 / 8.0,
+            0.1 * col as f64,
+            -(row as f64),
+        )
+    };
+
+    let path = std::env::temp_dir().join(format!(
+        "ray_trace_test_{}_round_trip.exr",
+        std::process::id()
+    ));
+    let out: Box<dyn Write> = Box::new(std::fs::File::create(&path).unwrap());
+    let mut writer = Box::new(ExrWriter::new(
+        BufWriter::new(out),
+        width,
+        height,
+        ExrPrecision::Half,
+        ExrCompression::Zip,
+    ));
+    for row in 0..height {
+        let pixels: Vec<Radiance> = (0..width).map(|col| pixel(row, col)).collect();
+        writer.write_row(row, &pixels).unwrap();
+    }
+    writer.finish().unwrap();
+    let bytes = std::fs::read(&path).unwrap();
+    std::fs::remove_file(path).unwrap();
+
+    let read_u64 = |at: usize| u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap()) as usize;
+    let read_i32 = |at: usize| i32::from_le_bytes(bytes[at..at + 4].try_into().unwrap());
+    let header_end = writer_header_len(&bytes);
+    let blocks = height.div_ceil(16);
+    assert_eq!(blocks, 3);
+
+    let mut halves = vec![];
+    for block in 0..blocks {
+        let offset = read_u64(header_end + block * 8);
+        assert_eq!(read_i32(offset), (block * 16) as i32);
+        let size = read_i32(offset + 4) as usize;
+        let lines = (height - block * 16).min(16);
+        assert!(
+            size < lines * width * 3 * 2,
+            "block {} not compressed",
+            block
+        );
+
+        let predicted =
+            miniz_oxide::inflate::decompress_to_vec_zlib(&bytes[offset + 8..offset + 8 + size])
+                .unwrap();
+        // undo the delta, then put the two halves back interleaved
+        let mut shuffled = predicted;
+        for index in 1..shuffled.len() {
+            shuffled[index] = shuffled[index]
+                .wrapping_add(shuffled[index - 1])
+                .wrapping_sub(128);
+        }
+        let half = shuffled.len().div_ceil(2);
+        let data: Vec<u8> = (0..shuffled.len())
+            .map(|index| {
+                if index % 2 == 0 {
+                    shuffled[index / 2]
+                } else {
+                    shuffled[half + index / 2]
+                }
+            })
+            .collect();
+        assert_eq!(data.len(), lines * width * 3 * 2);
+        halves.extend(
+            data.chunks(2)
+                .map(|pair| u16::from_le_bytes([pair[0], pair[1]])),
+        );
+    }
+
+    // every scanline is all of B, all of G, then all of R
+    for row in 0..height {
+        for col in 0..width {
+            for (index, channel) in [2, 1, 0].iter().enumerate() {
+                let expected = f32_to_half(pixel(row, col)[*channel] as f32);
+                let stored = halves[(row * 3 + index) * width + col];
+                assert_eq!(
+                    stored, expected,
+                    "row {} col {} channel {}",
+                    row, col, channel
+                );
+            }
+        }
+    }
+    assert_eq!(halves[(36 * 3 + 2) * width + 4], 0x5090); // R of the last pixel, 36.5
+    assert_eq!(halves[(20 * 3) * width + 1], 0xcd00); // B, -20
+}
@@ -0,0 +1,142 @@
+use crate::common::vec3::Radiance;
+use crate::render::adaptive;
+use crate::render::image_sink::ImageSink;
+use crate::render::quantize::ColorEncoding;
+
+// a rendered picture, linear radiance row-major from the top left
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Image {
+    width: usize,
+    height: usize,
+    pixels: Vec<Radiance>,
+    samples: Vec<usize>, // per pixel, empty when unknown
+}
+
+impl Image {
+    pub fn new(width: usize, height: usize, pixels: Vec<Radiance>) -> Self {
+        assert_eq!(pixels.len(), width * height, "wrong pixel count");
+        Self {
+            width,
+            height,
+            pixels,
+            samples: vec![],
+        }
+    }
+
+    pub fn with_samples(mut self, samples: Vec<usize>) -> Self {
+        assert!(samples.is_empty() || samples.len() == self.pixels.len());
+        self.samples = samples;
+        self
+    }
+
+    pub const fn width(&self) -> usize {
+        self.width
+    }
+
+    pub const fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn pixels(&self) -> &[Radiance] {
+        &self.pixels
+    }
+
+    pub fn into_pixels(self) -> Vec<Radiance> {
+        self.pixels
+    }
+
+    pub fn samples(&self) -> &[usize] {
+        &self.samples
+    }
+
+    pub fn pixel(&self, row: usize, col: usize) -> &Radiance {
+        assert!(col < self.width, "column {} out of {}", col, self.width);
+        &self.pixels[row * self.width + col]
+    }
+
+    pub fn row(&self, row: usize) -> &[Radiance] {
+        &self.pixels[row * self.width..(row + 1) * self.width]
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[Radiance]> {
+        // `max(1)` so a zero width image has no rows instead of a panic
+        self.pixels.chunks(self.width.max(1))
+    }
+
+    // samples spent per pixel from blue (none) to red (the most), to see where
+    // adaptive sampling worked hardest. `None` when the counts are unknown
+    pub fn heatmap(&self) -> Option<Self> {
+        let max = *self.samples.iter().max()?;
+        let pixels = self
+            .samples
+            .iter()
+            .map(|&samples| adaptive::heat(samples, max))
+            .collect();
+        Some(Self::new(self.width, self.height, pixels))
+    }
+
+    // interleaved rgb bytes, as they would be stored in an 8 bit file
+    pub fn to_rgb8(&self, encoding: &ColorEncoding) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.pixels.len() * 3);
+        for (row, pixels) in self.rows().enumerate() {
+            for (col, pixel) in pixels.iter().enumerate() {
+                let (r, g, b) = encoding.encode_u8(pixel, row, col);
+                bytes.extend_from_slice(&[r, g, b]);
+            }
+        }
+        bytes
+    }
+
+    // replays the picture into `sink`, e.g. a `FileSink` to save it
+    pub fn write_to<S: ImageSink + ?Sized>(
+        &self,
+        sink: &mut S,
+        encoding: &ColorEncoding,
+    ) -> std::io::Result<()> {
+        sink.begin(self.width, self.height, encoding)?;
+        for (row, pixels) in self.rows().enumerate() {
+            sink.row(row, pixels)?;
+        }
+        sink.finish()
+    }
+}
+
+////////// UT //////////
+#[test]
+fn test_accessors() {
+    let pixels = (0..6).map(|i| Radiance::new(i as f64, 0.0, 0.0)).collect();
+    let image = Image::new(3, 2, pixels);
+    assert_eq!(image.pixel(1, 0), &Radiance::new(3.0, 0.0, 0.0));
+    assert_eq!(image.row(1).len(), 3);
+    assert_eq!(image.rows().count(), 2);
+}
+
+#[test]
+fn test_to_rgb8() {
+    use crate::render::quantize::{Dither, TransferFunction};
+    use crate::render::tone_mapping::ToneMapping;
+
+    let encoding = ColorEncoding {
+        exposure: 0.0,
+        tone_mapping: ToneMapping::Clamp,
+        transfer: TransferFunction::Linear,
+        dither: Dither::None,
+    };
+    let image = Image::new(
+        2,
+        1,
+        vec![Radiance::new(0.0, 0.5, 1.0), Radiance::new(2.0, 1.0, 0.0)],
+    );
+    assert_eq!(image.to_rgb8(&encoding), vec![0, 128, 255, 255, 255, 0]);
+}
+
+#[test]
+fn test_heatmap() {
+    let image = Image::new(2, 1, vec![Radiance::default(); 2]);
+    assert!(image.heatmap().is_none());
+    let heatmap = image.with_samples(vec![0, 16]).heatmap().unwrap();
+    assert_eq!(
+        heatmap.pixels(),
+        &[Radiance::new(0.0, 0.0, 1.0), Radiance::new(1.0, 0.0, 0.0)]
+    );
+}
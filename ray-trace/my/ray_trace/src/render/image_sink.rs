@@ -0,0 +1,223 @@
+use crate::common::vec3::Radiance;
+use crate::render::image::Image;
+use crate::render::output_format::{OutputFormat, RowWriter};
+use crate::render::quantize::ColorEncoding;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+// where the painter puts the rendered image. `begin` is called once before
+// any row, then every row exactly once top to bottom, then `finish`.
+// Pixels are linear radiance, `encoding` is how the picture should be shown.
+// `samples` gets how many samples each pixel of a row took, right before `row`
+pub trait ImageSink {
+    fn begin(
+        &mut self,
+        _width: usize,
+        _height: usize,
+        _encoding: &ColorEncoding,
+    ) -> std::io::Result<()> {
+        Ok(())
+    }
+
+    fn samples(&mut self, _row: usize, _samples: &[usize]) -> std::io::Result<()> {
+        Ok(())
+    }
+
+    fn row(&mut self, row: usize, pixels: &[Radiance]) -> std::io::Result<()>;
+
+    fn finish(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+////////// file //////////
+// an image file, the format is picked by extension unless given.
+// No path renders into nothing, handy for benchmarks
+#[derive(Default)]
+pub struct FileSink {
+    path: Option<PathBuf>,
+    format: Option<OutputFormat>,
+    writer: Option<Box<dyn RowWriter>>,
+}
+
+impl FileSink {
+    pub fn new<P: AsRef<Path>>(path: Option<P>) -> Self {
+        Self {
+            path: path.map(|path| path.as_ref().to_path_buf()),
+            format: None,
+            writer: None,
+        }
+    }
+
+    pub const fn format(mut self, format: OutputFormat) -> Self {
+        self.format = Some(format);
+        self
+    }
+
+    fn output_format(&self) -> std::io::Result<OutputFormat> {
+        if let Some(format) = self.format {
+            return Ok(format);
+        }
+        match &self.path {
+            Some(path) if path.extension().is_some() => {
+                OutputFormat::from_path(path).ok_or_else(|| {
+                    std::io::Error::new(
+                        std::io::ErrorKind::InvalidInput,
+                        format!("no output format for {}", path.display()),
+                    )
+                })
+            }
+            _ => Ok(OutputFormat::default()),
+        }
+    }
+}
+
+impl ImageSink for FileSink {
+    fn begin(
+        &mut self,
+        width: usize,
+        height: usize,
+        encoding: &ColorEncoding,
+    ) -> std::io::Result<()> {
+        let format = self.output_format()?;
+        let file: BufWriter<Box<dyn Write>> = if let Some(path) = &self.path {
+            BufWriter::new(Box::new(File::create(path)?))
+        } else {
+            BufWriter::new(Box::new(std::io::sink()))
+        };
+        self.writer = Some(format.create_writer(file, width, height, *encoding)?);
+        Ok(())
+    }
+
+    fn row(&mut self, row: usize, pixels: &[Radiance]) -> std::io::Result<()> {
+        match &mut self.writer {
+            Some(writer) => writer.write_row(row, pixels),
+            None => Err(not_begun()),
+        }
+    }
+
+    fn finish(&mut self) -> std::io::Result<()> {
+        match self.writer.take() {
+            Some(writer) => writer.finish(),
+            None => Err(not_begun()),
+        }
+    }
+}
+
+fn not_begun() -> std::io::Error {
+    std::io::Error::other("image sink used before `begin`")
+}
+
+////////// memory //////////
+// keeps the whole linear framebuffer, row-major
+#[derive(Debug, Default)]
+pub struct VecSink {
+    width: usize,
+    height: usize,
+    pixels: Vec<Radiance>,
+    samples: Vec<usize>,
+}
+
+impl VecSink {
+    pub const fn width(&self) -> usize {
+        self.width
+    }
+
+    pub const fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn pixels(&self) -> &[Radiance] {
+        &self.pixels
+    }
+
+    pub fn into_pixels(self) -> Vec<Radiance> {
+        self.pixels
+    }
+
+    pub fn samples(&self) -> &[usize] {
+        &self.samples
+    }
+
+    pub fn into_image(self) -> Image {
+        Image::new(self.width, self.height, self.pixels).with_samples(self.samples)
+    }
+}
+
+impl ImageSink for VecSink {
+    fn begin(
+        &mut self,
+        width: usize,
+        height: usize,
+        _encoding: &ColorEncoding,
+    ) -> std::io::Result<()> {
+        self.width = width;
+        self.height = height;
+        self.pixels = Vec::with_capacity(width * height);
+        self.samples = Vec::with_capacity(width * height);
+        Ok(())
+    }
+
+    fn samples(&mut self, _row: usize, samples: &[usize]) -> std::io::Result<()> {
+        self.samples.extend_from_slice(samples);
+        Ok(())
+    }
+
+    fn row(&mut self, _row: usize, pixels: &[Radiance]) -> std::io::Result<()> {
+        self.pixels.extend_from_slice(pixels);
+        Ok(())
+    }
+}
+
+////////// callback //////////
+// hands every row to a closure, e.g. to show progress in a window
+pub struct CallbackSink<F> {
+    on_row: F,
+}
+
+impl<F> CallbackSink<F>
+where
+    F: FnMut(usize, &[Radiance]),
+{
+    pub const fn new(on_row: F) -> Self {
+        Self { on_row }
+    }
+}
+
+impl<F> ImageSink for CallbackSink<F>
+where
+    F: FnMut(usize, &[Radiance]),
+{
+    fn row(&mut self, row: usize, pixels: &[Radiance]) -> std::io::Result<()> {
+        (self.on_row)(row, pixels);
+        Ok(())
+    }
+}
+
+////////// UT //////////
+#[test]
+fn test_format_by_extension() {
+    assert_eq!(
+        FileSink::new(Some("a.png")).output_format().unwrap(),
+        OutputFormat::Png {
+            sixteen_bit: false,
+            alpha: false
+        }
+    );
+    assert_eq!(
+        FileSink::new(None::<&Path>).output_format().unwrap(),
+        OutputFormat::PpmAscii
+    );
+    assert!(FileSink::new(Some("a.jpg")).output_format().is_err());
+
+    let sink = FileSink::new(Some("a.ppm")).format(OutputFormat::PpmBinary);
+    assert_eq!(sink.output_format().unwrap(), OutputFormat::PpmBinary);
+}
+
+#[test]
+fn test_file_sink_needs_begin() {
+    let mut sink = FileSink::new(None::<&Path>);
+    assert!(sink.row(0, &[Radiance::default()]).is_err());
+    assert!(sink.finish().is_err());
+}
@@ -1,3 +1,16 @@
-pub(crate) mod camera;
+pub mod adaptive;
+pub mod aperture;
+pub mod camera;
+pub mod exr_writer;
+pub mod image;
+pub mod image_sink;
+pub mod output_format;
 mod painter;
-mod take_photo_settings;
+mod pfm_writer;
+mod png_writer;
+mod ppm_writer;
+pub mod quantize;
+pub mod sampler;
+pub mod take_photo_settings;
+pub mod tile;
+pub mod tone_mapping;
@@ -0,0 +1,116 @@
+use crate::common::vec3::Radiance;
+use crate::render::exr_writer::{ExrCompression, ExrPrecision, ExrWriter};
+use crate::render::pfm_writer::PfmWriter;
+use crate::render::png_writer::PngWriter;
+use crate::render::ppm_writer::PpmWriter;
+use crate::render::quantize::ColorEncoding;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum OutputFormat {
+    // P3, one "r g b" line per pixel, easy to read when debugging.
+    // What `.ppm` files get unless another format is asked for
+    #[default]
+    PpmAscii,
+    // P6, raw rgb bytes, only when set explicitly
+    PpmBinary,
+    // P5, raw grey bytes, for single channel passes
+    PgmBinary,
+    // alpha is always opaque, the renderer has no coverage
+    Png {
+        sixteen_bit: bool,
+        alpha: bool,
+    },
+    // the formats below keep the linear radiance, no exposure or tone mapping
+    // Portable Float Map, 32 bit floats
+    Pfm,
+    // OpenEXR scanline
+    Exr {
+        precision: ExrPrecision,
+        compression: ExrCompression,
+    },
+}
+
+impl OutputFormat {
+    // by file extension, `None` if we can't write it
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "ppm" => Some(Self::PpmAscii),
+            "pgm" => Some(Self::PgmBinary),
+            "png" => Some(Self::Png {
+                sixteen_bit: false,
+                alpha: false,
+            }),
+            "pfm" => Some(Self::Pfm),
+            "exr" => Some(Self::Exr {
+                precision: ExrPrecision::Half,
+                compression: ExrCompression::Zip,
+            }),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn create_writer(
+        &self,
+        file: BufWriter<Box<dyn Write>>,
+        width: usize,
+        height: usize,
+        encoding: ColorEncoding,
+    ) -> std::io::Result<Box<dyn RowWriter>> {
+        Ok(match *self {
+            Self::PpmAscii | Self::PpmBinary | Self::PgmBinary => {
+                Box::new(PpmWriter::new(file, *self, width, height, encoding)?)
+            }
+            Self::Png { sixteen_bit, alpha } => Box::new(PngWriter::new(
+                file,
+                width,
+                height,
+                encoding,
+                sixteen_bit,
+                alpha,
+            )?),
+            Self::Pfm => Box::new(PfmWriter::new(file, width, height)),
+            Self::Exr {
+                precision,
+                compression,
+            } => Box::new(ExrWriter::new(file, width, height, precision, compression)),
+        })
+    }
+}
+
+// receives the rendered rows top to bottom
+pub(crate) trait RowWriter {
+    fn write_row(&mut self, row: usize, pixels: &[Radiance]) -> std::io::Result<()>;
+    fn finish(self: Box<Self>) -> std::io::Result<()>;
+}
+
+////////// UT //////////
+#[test]
+fn test_from_path() {
+    assert_eq!(
+        OutputFormat::from_path(Path::new("out/001.ppm")),
+        Some(OutputFormat::PpmAscii)
+    );
+    assert_eq!(
+        OutputFormat::from_path(Path::new("depth.PGM")),
+        Some(OutputFormat::PgmBinary)
+    );
+    assert_eq!(
+        OutputFormat::from_path(Path::new("a.png")),
+        Some(OutputFormat::Png {
+            sixteen_bit: false,
+            alpha: false
+        })
+    );
+    assert_eq!(
+        OutputFormat::from_path(Path::new("a.exr")),
+        Some(OutputFormat::Exr {
+            precision: ExrPrecision::Half,
+            compression: ExrCompression::Zip
+        })
+    );
+    assert_eq!(OutputFormat::from_path(Path::new("a.jpg")), None);
+    assert_eq!(OutputFormat::from_path(Path::new("a")), None);
+}
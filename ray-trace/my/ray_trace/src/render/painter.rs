@@ -1,28 +1,38 @@
-use crate::common::color::Color;
-use crate::common::ray::Ray;
-use crate::common::vec3::Vec3;
-use crate::render::take_photo_settings::TakePhotoSettings;
+use crate::common::random;
+use crate::common::vec3::Radiance;
+use crate::render::adaptive::{AdaptiveSampling, PixelEstimate};
+use crate::render::camera::CameraSample;
+use crate::render::image_sink::ImageSink;
+use crate::render::quantize::{ColorEncoding, Dither, TransferFunction};
+use crate::render::sampler::{self, SamplePattern, Sampler};
+use crate::render::tile::{self, Tile, TileOrder};
+use crate::render::tone_mapping::ToneMapping;
 use log::info;
-use rand::{thread_rng, Rng};
-use std::fs::File;
-use std::io::{BufWriter, Write};
-use std::path::Path;
-use std::process::Output;
-use std::sync::atomic::AtomicBool;
-use std::sync::atomic::Ordering;
+use rayon::prelude::*;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
 
 #[derive(Debug)]
 pub struct Painter {
     pub width: usize,
     pub height: usize,
     samples: usize,
-    gamma: bool,
-    // threads
-    // parallel
+    adaptive: Option<AdaptiveSampling>, // overrides `samples`
+    sampler: SamplePattern,
+    seed: u64,
+    transfer: TransferFunction,
+    dither: Dither,
+    exposure: f64, // in stops
+    tone_mapping: ToneMapping,
+    threads: usize, // 0 means one per cpu
+    parallel: bool,
+    tile_size: usize, // 0 means render by rows
+    tile_order: TileOrder,
 }
 
-struct PainterOutputContext<'c> {
-    file: BufWriter<Box<dyn Write>>,
+struct PainterOutputContext<'c, S: ?Sized> {
+    sink: &'c mut S,
     cancel: &'c AtomicBool,
 }
 
@@ -32,12 +42,37 @@ impl Painter {
             width,
             height,
             samples: 50,
-            gamma: true,
+            adaptive: None,
+            sampler: SamplePattern::Independent,
+            seed: 0,
+            transfer: TransferFunction::Gamma(2.0),
+            dither: Dither::None,
+            exposure: 0.0,
+            tone_mapping: ToneMapping::Clamp,
+            threads: 0,
+            parallel: true,
+            tile_size: 0,
+            tile_order: TileOrder::Scanline,
         }
     }
 
-    pub const fn gamma(mut self, gamma: bool) -> Self {
-        self.gamma = gamma;
+    pub const fn transfer(mut self, transfer: TransferFunction) -> Self {
+        self.transfer = transfer;
+        self
+    }
+
+    pub const fn dither(mut self, dither: Dither) -> Self {
+        self.dither = dither;
+        self
+    }
+
+    pub const fn exposure(mut self, exposure: f64) -> Self {
+        self.exposure = exposure;
+        self
+    }
+
+    pub const fn tone_mapping(mut self, tone_mapping: ToneMapping) -> Self {
+        self.tone_mapping = tone_mapping;
         self
     }
 
@@ -45,73 +80,143 @@ impl Painter {
         self.samples = samples;
         self
     }
-    ////////// output file //////////
-    fn create_output_file(
-        &self,
-        path: Option<&Path>,
-    ) -> std::io::Result<BufWriter<Box<dyn Write>>> {
-        let mut file: BufWriter<Box<dyn Write>> = if let Some(path) = path {
-            BufWriter::new(Box::new(File::create(&path)?))
-        } else {
-            BufWriter::new(Box::new(std::io::sink()))
-        };
 
-        write!(
-            &mut file,
-            "P3\n{width} {height}\n255\n",
-            width = self.width,
-            height = self.height
-        )?;
+    pub const fn adaptive(mut self, adaptive: AdaptiveSampling) -> Self {
+        self.adaptive = Some(adaptive);
+        self
+    }
 
-        Ok(file)
+    pub const fn sampler(mut self, sampler: SamplePattern) -> Self {
+        self.sampler = sampler;
+        self
     }
 
-    fn create_output_context<'c>(
-        &self,
-        path: Option<&Path>,
-        cancel: &'c AtomicBool,
-    ) -> std::io::Result<PainterOutputContext<'c>> {
-        let file = self.create_output_file(path)?;
-        Ok(PainterOutputContext { file, cancel })
+    // same seed, same picture, whatever the thread count or tiling
+    pub const fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub const fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    pub const fn parallel(mut self, parallel: bool) -> Self {
+        self.parallel = parallel;
+        self
+    }
+
+    // `size` 0 goes back to rendering by rows
+    pub const fn tiles(mut self, size: usize, order: TileOrder) -> Self {
+        self.tile_size = size;
+        self.tile_order = order;
+        self
+    }
+
+    fn thread_count(&self) -> usize {
+        if !self.parallel {
+            1
+        } else if self.threads == 0 {
+            num_cpus::get()
+        } else {
+            self.threads
+        }
+    }
+
+    fn thread_pool(&self) -> std::io::Result<rayon::ThreadPool> {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(self.thread_count())
+            .build()
+            .map_err(std::io::Error::other)
+    }
+
+    ////////// output //////////
+    pub const fn color_encoding(&self) -> ColorEncoding {
+        ColorEncoding {
+            exposure: self.exposure,
+            tone_mapping: self.tone_mapping,
+            transfer: self.transfer,
+            dither: self.dither,
+        }
     }
 
     ///////// pixel //////////
-    fn calculate_uv(&self, row: usize, col: usize) -> (f64, f64) {
-        if self.samples == 1 {
+    // the samples a pixel takes at most, the sampler stratifies over these
+    fn sample_count(&self) -> usize {
+        match &self.adaptive {
+            Some(adaptive) => adaptive.max_samples,
+            None => self.samples,
+        }
+    }
+
+    // differs by seed, so the sample patterns change with it too
+    fn pixel_id(&self, row: usize, col: usize) -> u64 {
+        sampler::hash(&[self.seed, (row * self.width + col) as u64])
+    }
+
+    // every sample draws from its own stream
+    fn seed_sample(&self, row: usize, col: usize, index: usize) {
+        random::seed(sampler::hash(&[self.pixel_id(row, col), index as u64]));
+    }
+
+    fn camera_sample(&self, row: usize, col: usize, index: usize) -> CameraSample {
+        if self.samples == 1 && self.adaptive.is_none() {
+            // the pixel corner, but lens and shutter are still sampled so
+            // depth of field and motion blur do not vanish
             let u = (col as f64) / self.width as f64;
             let v = ((self.height - 1 - row) as f64) / self.height as f64;
-            (u, v)
+            let pixel = self.pixel_id(row, col);
+            CameraSample {
+                u,
+                v,
+                lens: self.sampler.get_2d(pixel, index, 1, 1),
+                time: self.sampler.get_2d(pixel, index, 1, 2).0,
+            }
         } else {
             // 采样时，加扰动
             // > The “less than” before the 1 is important as we will sometimes take advantage of that.
-            let u = (col as f64 + thread_rng().gen_range(0.0, 1.0)) / self.width as f64;
-            let v = ((self.height - 1 - row) as f64 + thread_rng().gen_range(0.0, 1.0))
-                / self.height as f64;
-            (u, v)
+            let pixel = self.pixel_id(row, col);
+            let count = self.sample_count();
+            let (du, dv) = self.sampler.get_2d(pixel, index, count, 0);
+            let u = (col as f64 + du) / self.width as f64;
+            let v = ((self.height - 1 - row) as f64 + dv) / self.height as f64;
+            CameraSample {
+                u,
+                v,
+                lens: self.sampler.get_2d(pixel, index, count, 1),
+                time: self.sampler.get_2d(pixel, index, count, 2).0,
+            }
         }
     }
-    fn render_pixel<F>(&self, row: usize, col: usize, uv_color: &F) -> (u8, u8, u8)
+    // average radiance of the samples, still linear, and how many were taken
+    fn render_pixel<F>(&self, row: usize, col: usize, uv_color: &F) -> (Radiance, usize)
     where
-        F: Fn(f64, f64) -> Color + Send + Sync,
+        F: Fn(&CameraSample) -> Radiance + Send + Sync,
     {
-        let color_need_average: Vec3 = (0..self.samples)
-            .map(|_| {
-                let (u, v) = self.calculate_uv(row, col);
-                uv_color(u, v)
+        if let Some(adaptive) = &self.adaptive {
+            let mut estimate = PixelEstimate::default();
+            while !adaptive.done(&estimate) {
+                self.seed_sample(row, col, estimate.count());
+                let sample = self.camera_sample(row, col, estimate.count());
+                estimate.add(uv_color(&sample));
+            }
+            return (estimate.mean(), estimate.count());
+        }
+
+        let color_need_average: Radiance = (0..self.samples)
+            .map(|index| {
+                self.seed_sample(row, col, index);
+                uv_color(&self.camera_sample(row, col, index))
             })
-            .map(|e| e.into())
             .sum();
 
-        let color = color_need_average
-            .into_color(self.samples, self.gamma)
-            .int_form()
-            .into_owned();
-        (color.r, color.g, color.b)
+        (color_need_average / self.samples as f64, self.samples)
     }
 
-    fn seq_render_row<F>(&self, row: usize, uv_color: &F) -> Vec<(u8, u8, u8)>
+    fn seq_render_row<F>(&self, row: usize, uv_color: &F) -> Vec<(Radiance, usize)>
     where
-        F: Fn(f64, f64) -> Color + Send + Sync,
+        F: Fn(&CameraSample) -> Radiance + Send + Sync,
     {
         (0..self.width)
             .map(|col| self.render_pixel(row, col, &uv_color))
@@ -121,60 +226,302 @@ impl Painter {
     fn seq_render_row_iter<'c, F>(
         &'c self,
         uv_color: F,
-    ) -> impl Iterator<Item = Vec<(u8, u8, u8)>> + 'c
+    ) -> impl Iterator<Item = Vec<(Radiance, usize)>> + 'c
     where
-        F: Fn(f64, f64) -> Color + Send + Sync + 'c,
+        F: Fn(&CameraSample) -> Radiance + Send + Sync + 'c,
     {
         (0..self.height).map(move |row| self.seq_render_row(row, &uv_color))
     }
 
-    /////////// pixels to file ///////
-    fn do_row_pixels_to_file(
-        context: &mut PainterOutputContext<'_>,
-        pixels: Vec<(u8, u8, u8)>,
-    ) -> std::io::Result<()> {
-        for pixel in pixels {
-            writeln!(context.file, "{} {} {}", pixel.0, pixel.1, pixel.2)?;
+    // rows are rendered out of order on the pool, then sent back to the
+    // calling thread, which writes them to the file in order
+    fn parallel_render_rows_to_file<S, F>(
+        &self,
+        context: &mut PainterOutputContext<'_, S>,
+        uv_color: F,
+    ) -> std::io::Result<()>
+    where
+        S: ImageSink + ?Sized,
+        F: Fn(&CameraSample) -> Radiance + Send + Sync,
+    {
+        let pool = self.thread_pool()?;
+        let cancel = context.cancel;
+        let (sender, receiver) = mpsc::channel();
+
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                pool.install(|| {
+                    (0..self.height)
+                        .into_par_iter()
+                        .for_each_with(sender, |sender, row| {
+                            if cancel.load(Ordering::Relaxed) {
+                                return;
+                            }
+                            let pixels = self.seq_render_row(row, &uv_color);
+                            // receiver only hangs up after an io error
+                            let _ = sender.send((row, pixels));
+                        })
+                })
+            });
+
+            let mut pending = BTreeMap::new();
+            let mut next_row = 0;
+            for (row, pixels) in receiver {
+                pending.insert(row, pixels);
+                while let Some(pixels) = pending.remove(&next_row) {
+                    self.row_pixels_to_file(context, next_row, pixels)?;
+                    next_row += 1;
+                }
+            }
+            Ok(())
+        })
+    }
+
+    fn render_tile<F>(&self, tile: &Tile, uv_color: &F) -> Vec<(Radiance, usize)>
+    where
+        F: Fn(&CameraSample) -> Radiance + Send + Sync,
+    {
+        let mut pixels = Vec::with_capacity(tile.width() * tile.height());
+        for row in tile.rows.clone() {
+            for col in tile.cols.clone() {
+                pixels.push(self.render_pixel(row, col, uv_color));
+            }
         }
-        context.file.flush()
+        pixels
     }
 
-    fn row_pixels_to_file(
+    // tiles are shared out on the same pool as rows. A row is written as soon
+    // as every tile across it is done
+    fn parallel_render_tiles_to_file<S, F>(
         &self,
-        context: &mut PainterOutputContext<'_>,
+        context: &mut PainterOutputContext<'_, S>,
+        uv_color: F,
+    ) -> std::io::Result<()>
+    where
+        S: ImageSink + ?Sized,
+        F: Fn(&CameraSample) -> Radiance + Send + Sync,
+    {
+        let pool = self.thread_pool()?;
+        let cancel = context.cancel;
+        let tiles = tile::tiles(self.width, self.height, self.tile_size, self.tile_order);
+        let (sender, receiver) = mpsc::channel();
+
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                pool.install(|| {
+                    tiles
+                        .par_iter()
+                        .enumerate()
+                        .for_each_with(sender, |sender, (index, tile)| {
+                            if cancel.load(Ordering::Relaxed) {
+                                return;
+                            }
+                            let pixels = self.render_tile(tile, &uv_color);
+                            // receiver only hangs up after an io error
+                            let _ = sender.send((index, pixels));
+                        })
+                })
+            });
+
+            let mut framebuffer = vec![(Radiance::default(), 0); self.width * self.height];
+            let mut missing = vec![0; self.height];
+            for tile in &tiles {
+                tile.rows.clone().for_each(|row| missing[row] += 1);
+            }
+            let mut remaining = tiles.len();
+            let mut next_row = 0;
+            for (index, pixels) in receiver {
+                let tile = &tiles[index];
+                for (row, tile_row) in tile.rows.clone().zip(pixels.chunks(tile.width())) {
+                    let start = row * self.width + tile.cols.start;
+                    framebuffer[start..start + tile.width()].clone_from_slice(tile_row);
+                    missing[row] -= 1;
+                }
+                remaining -= 1;
+                info!("Tile remaining: {}", remaining);
+
+                while next_row < self.height && missing[next_row] == 0 {
+                    let start = next_row * self.width;
+                    let pixels = framebuffer[start..start + self.width].to_vec();
+                    self.row_pixels_to_file(context, next_row, pixels)?;
+                    next_row += 1;
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /////////// pixels to file ///////
+    fn row_pixels_to_file<S: ImageSink + ?Sized>(
+        &self,
+        context: &mut PainterOutputContext<'_, S>,
         row: usize,
-        pixels: Vec<(u8, u8, u8)>,
+        pixels: Vec<(Radiance, usize)>,
     ) -> std::io::Result<()> {
         info!("Scan line remaining: {}", self.height - row);
-        Self::do_row_pixels_to_file(context, pixels).map_err(|e| {
-            context.cancel.store(true, Ordering::Relaxed);
-            e
-        })
+        let (pixels, samples): (Vec<_>, Vec<_>) = pixels.into_iter().unzip();
+        context
+            .sink
+            .samples(row, &samples)
+            .and_then(|_| context.sink.row(row, &pixels))
+            .inspect_err(|_| {
+                context.cancel.store(true, Ordering::Relaxed);
+            })
     }
 
-    pub fn draw<P, F>(&self, path: &Option<P>, uv_color: F) -> std::io::Result<()>
+    pub fn draw<S, F>(&self, sink: &mut S, uv_color: F) -> std::io::Result<()>
     where
-        P: AsRef<Path>,
-        F: Fn(f64, f64) -> Color + Send + Sync,
+        S: ImageSink + ?Sized,
+        F: Fn(&CameraSample) -> Radiance + Send + Sync,
     {
-        let path = match path {
-            Some(ref path) => Some(path.as_ref()),
-            None => None,
+        sink.begin(self.width, self.height, &self.color_encoding())?;
+
+        let cancel = AtomicBool::new(false);
+        let mut context = PainterOutputContext {
+            sink,
+            cancel: &cancel,
         };
 
-        if
-        /*self.parallel*/
-        false {
-            unimplemented!()
+        if self.tile_size > 0 {
+            self.parallel_render_tiles_to_file(&mut context, uv_color)?;
+        } else if self.parallel {
+            self.parallel_render_rows_to_file(&mut context, uv_color)?;
         } else {
-            let cancel = AtomicBool::new(false);
-            let mut context = self.create_output_context(path, &cancel)?;
-
             for (row, pixels) in self.seq_render_row_iter(uv_color).enumerate() {
                 self.row_pixels_to_file(&mut context, row, pixels)?;
             }
+        }
 
-            Ok(())
+        context.sink.finish()
+    }
+}
+
+////////// UT //////////
+#[test]
+fn test_parallel_rows_in_order() {
+    use crate::render::image_sink::VecSink;
+    let uv_color = |sample: &CameraSample| Radiance::new(sample.u, sample.v, 0.5);
+
+    let mut seq = VecSink::default();
+    Painter::new(16, 9)
+        .samples(1)
+        .parallel(false)
+        .draw(&mut seq, uv_color)
+        .unwrap();
+    let mut par = VecSink::default();
+    Painter::new(16, 9)
+        .samples(1)
+        .parallel(true)
+        .threads(4)
+        .draw(&mut par, uv_color)
+        .unwrap();
+
+    assert_eq!(seq.pixels().len(), 16 * 9);
+    assert_eq!(seq.pixels(), par.pixels());
+}
+
+#[test]
+fn test_tiles_same_as_rows() {
+    use crate::render::image_sink::VecSink;
+    let uv_color = |sample: &CameraSample| Radiance::new(sample.u, sample.v, 0.5);
+    let mut rows = VecSink::default();
+    Painter::new(37, 21)
+        .samples(1)
+        .draw(&mut rows, uv_color)
+        .unwrap();
+
+    for order in [TileOrder::Scanline, TileOrder::Spiral, TileOrder::Hilbert] {
+        let mut tiles = VecSink::default();
+        Painter::new(37, 21)
+            .samples(1)
+            .tiles(8, order)
+            .draw(&mut tiles, uv_color)
+            .unwrap();
+        assert_eq!(rows.pixels(), tiles.pixels(), "{:?}", order);
+    }
+}
+
+#[test]
+fn test_tiles_stop_after_io_error() {
+    use std::sync::atomic::AtomicUsize;
+
+    struct FailingSink<'f>(&'f AtomicBool);
+    impl ImageSink for FailingSink<'_> {
+        fn row(&mut self, _row: usize, _pixels: &[Radiance]) -> std::io::Result<()> {
+            self.0.store(true, Ordering::Relaxed);
+            Err(std::io::Error::other("disk full"))
         }
     }
+
+    // the bottom tiles wait for the failed write, so only they can be skipped.
+    // The wait is bounded: should the pool take a bottom tile first, the render
+    // runs to the end and the count below fails instead of the test hanging
+    let failed = AtomicBool::new(false);
+    let rendered = AtomicUsize::new(0);
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+    let result = Painter::new(16, 16)
+        .samples(1)
+        .threads(1)
+        .tiles(8, TileOrder::Scanline)
+        .draw(&mut FailingSink(&failed), |sample| {
+            while sample.v < 0.5
+                && !failed.load(Ordering::Relaxed)
+                && std::time::Instant::now() < deadline
+            {
+                std::thread::yield_now();
+            }
+            rendered.fetch_add(1, Ordering::Relaxed);
+            Radiance::default()
+        });
+    assert!(result.is_err());
+    assert!(rendered.load(Ordering::Relaxed) < 16 * 16);
+}
+
+#[test]
+fn test_callback_rows_top_to_bottom() {
+    use crate::render::image_sink::CallbackSink;
+    let mut seen = vec![];
+    let mut sink = CallbackSink::new(|row, pixels: &[Radiance]| seen.push((row, pixels.len())));
+    Painter::new(5, 4)
+        .samples(1)
+        .threads(3)
+        .draw(&mut sink, |sample| Radiance::new(sample.u, sample.v, 0.0))
+        .unwrap();
+    assert_eq!(seen, (0..4).map(|row| (row, 5)).collect::<Vec<_>>());
+}
+
+#[test]
+fn test_adaptive_spends_samples_on_noise() {
+    use crate::render::image_sink::VecSink;
+    // left half flat, right half flickering between black and white. The
+    // painter seeds every sample, so the flicker is the same on every run
+    let uv_color = |sample: &CameraSample| {
+        if sample.u < 0.5 || random::gen_range(0.0, 1.0) < 0.5 {
+            Radiance::new(0.5, 0.5, 0.5)
+        } else {
+            Radiance::default()
+        }
+    };
+    let mut sink = VecSink::default();
+    Painter::new(4, 2)
+        .adaptive(AdaptiveSampling::new(32, 128, 0.001))
+        .draw(&mut sink, uv_color)
+        .unwrap();
+    assert_eq!(sink.samples(), &[32, 32, 128, 128, 32, 32, 128, 128]);
+}
+
+#[test]
+fn test_stratified_samplers_split_an_edge_evenly() {
+    use crate::render::image_sink::VecSink;
+    // half the pixel is lit, 64 samples stratified along u land 32 on each side
+    let uv_color = |sample: &CameraSample| Radiance::new((sample.u < 0.5) as u8 as f64, 0.0, 0.0);
+    for sampler in [SamplePattern::Sobol, SamplePattern::CorrelatedMultiJittered] {
+        let mut sink = VecSink::default();
+        Painter::new(1, 1)
+            .samples(64)
+            .sampler(sampler)
+            .draw(&mut sink, uv_color)
+            .unwrap();
+        assert_eq!(sink.pixels()[0].x, 0.5, "{:?}", sampler);
+    }
 }
@@ -0,0 +1,71 @@
+use crate::common::vec3::Radiance;
+use crate::render::output_format::RowWriter;
+use std::io::{BufWriter, Write};
+
+// Portable Float Map, linear radiance as 32 bit floats. Rows are stored
+// bottom to top, so everything is kept until `finish`
+pub(crate) struct PfmWriter {
+    file: BufWriter<Box<dyn Write>>,
+    width: usize,
+    height: usize,
+    rows: Vec<Vec<Radiance>>,
+}
+
+impl PfmWriter {
+    pub fn new(file: BufWriter<Box<dyn Write>>, width: usize, height: usize) -> Self {
+        Self {
+            file,
+            width,
+            height,
+            rows: Vec::with_capacity(height),
+        }
+    }
+}
+
+impl RowWriter for PfmWriter {
+    fn write_row(&mut self, _row: usize, pixels: &[Radiance]) -> std::io::Result<()> {
+        self.rows.push(pixels.to_vec());
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> std::io::Result<()> {
+        // a negative scale means little endian
+        write!(self.file, "PF\n{} {}\n-1.0\n", self.width, self.height)?;
+        for row in self.rows.iter().rev() {
+            let mut bytes = Vec::with_capacity(row.len() * 12);
+            for pixel in row {
+                for channel in [pixel.x, pixel.y, pixel.z] {
+                    bytes.extend_from_slice(&(channel as f32).to_le_bytes());
+                }
+            }
+            self.file.write_all(&bytes)?;
+        }
+        self.file.flush()
+    }
+}
+
+////////// UT //////////
+#[test]
+fn test_write() {
+    let path = std::env::temp_dir().join(format!("ray_trace_test_{}.pfm", std::process::id()));
+    let out: Box<dyn Write> = Box::new(std::fs::File::create(&path).unwrap());
+    let mut writer = Box::new(PfmWriter::new(BufWriter::new(out), 1, 2));
+    writer
+        .write_row(0, &[Radiance::new(2.5, 0.0, 0.0)])
+        .unwrap();
+    writer
+        .write_row(1, &[Radiance::new(0.0, 0.0, 1.0)])
+        .unwrap();
+    writer.finish().unwrap();
+    let bytes = std::fs::read(&path).unwrap();
+    std::fs::remove_file(path).unwrap();
+
+    let header = b"PF\n1 2\n-1.0\n";
+    assert_eq!(&bytes[..header.len()], header);
+    let floats: Vec<f32> = bytes[header.len()..]
+        .chunks(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect();
+    // bottom row first
+    assert_eq!(floats, vec![0.0, 0.0, 1.0, 2.5, 0.0, 0.0]);
+}
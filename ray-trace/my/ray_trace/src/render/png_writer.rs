@@ -0,0 +1,131 @@
+use crate::common::vec3::Radiance;
+use crate::render::output_format::RowWriter;
+use crate::render::quantize::ColorEncoding;
+use std::io::{BufWriter, Write};
+
+pub(crate) struct PngWriter {
+    stream: ::png::StreamWriter<'static, BufWriter<Box<dyn Write>>>,
+    encoding: ColorEncoding,
+    sixteen_bit: bool,
+    alpha: bool,
+}
+
+impl PngWriter {
+    pub fn new(
+        file: BufWriter<Box<dyn Write>>,
+        width: usize,
+        height: usize,
+        encoding: ColorEncoding,
+        sixteen_bit: bool,
+        alpha: bool,
+    ) -> std::io::Result<Self> {
+        let mut encoder = ::png::Encoder::new(file, width as u32, height as u32);
+        encoder.set_color(if alpha {
+            ::png::ColorType::Rgba
+        } else {
+            ::png::ColorType::Rgb
+        });
+        encoder.set_depth(if sixteen_bit {
+            ::png::BitDepth::Sixteen
+        } else {
+            ::png::BitDepth::Eight
+        });
+        let stream = encoder
+            .write_header()
+            .and_then(|writer| writer.into_stream_writer())
+            .map_err(std::io::Error::other)?;
+
+        Ok(Self {
+            stream,
+            encoding,
+            sixteen_bit,
+            alpha,
+        })
+    }
+}
+
+impl RowWriter for PngWriter {
+    fn write_row(&mut self, row: usize, pixels: &[Radiance]) -> std::io::Result<()> {
+        let mut bytes = vec![];
+        for (col, radiance) in pixels.iter().enumerate() {
+            // png stores 16 bits samples big endian
+            if self.sixteen_bit {
+                let (r, g, b) = self.encoding.encode_u16(radiance);
+                for sample in [r, g, b] {
+                    bytes.extend_from_slice(&sample.to_be_bytes());
+                }
+                if self.alpha {
+                    bytes.extend_from_slice(&u16::MAX.to_be_bytes());
+                }
+            } else {
+                let (r, g, b) = self.encoding.encode_u8(radiance, row, col);
+                bytes.extend_from_slice(&[r, g, b]);
+                if self.alpha {
+                    bytes.push(u8::MAX);
+                }
+            }
+        }
+        self.stream.write_all(&bytes)
+    }
+
+    fn finish(self: Box<Self>) -> std::io::Result<()> {
+        self.stream.finish().map_err(std::io::Error::other)
+    }
+}
+
+////////// UT //////////
+#[test]
+fn test_write() {
+    use crate::render::quantize::{Dither, TransferFunction};
+    use crate::render::tone_mapping::ToneMapping;
+
+    let encoding = ColorEncoding {
+        exposure: 0.0,
+        tone_mapping: ToneMapping::Clamp,
+        transfer: TransferFunction::Linear,
+        dither: Dither::None,
+    };
+    for (sixteen_bit, alpha) in [(false, false), (false, true), (true, false), (true, true)] {
+        let path = std::env::temp_dir().join(format!(
+            "ray_trace_test_{}_{}_{}.png",
+            std::process::id(),
+            sixteen_bit,
+            alpha
+        ));
+        let out: Box<dyn Write> = Box::new(std::fs::File::create(&path).unwrap());
+        let mut writer = Box::new(
+            PngWriter::new(BufWriter::new(out), 2, 2, encoding, sixteen_bit, alpha).unwrap(),
+        );
+        for row in 0..2 {
+            writer
+                .write_row(
+                    row,
+                    &[Radiance::new(1.0, 0.0, 0.5), Radiance::new(0.0, 1.0, 0.0)],
+                )
+                .unwrap();
+        }
+        writer.finish().unwrap();
+
+        let decoder = ::png::Decoder::new(std::fs::File::open(&path).unwrap());
+        let mut reader = decoder.read_info().unwrap();
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buffer).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!((info.width, info.height), (2, 2));
+        let channels = if alpha { 4 } else { 3 };
+        if sixteen_bit {
+            assert_eq!(info.bit_depth, ::png::BitDepth::Sixteen);
+            assert_eq!(&buffer[..6], &[0xff, 0xff, 0, 0, 0x80, 0x00]);
+        } else {
+            assert_eq!(info.bit_depth, ::png::BitDepth::Eight);
+            assert_eq!(&buffer[..3], &[255, 0, 128]);
+            assert_eq!(&buffer[channels..channels + 3], &[0, 255, 0]);
+        }
+        if alpha {
+            assert_eq!(info.color_type, ::png::ColorType::Rgba);
+        } else {
+            assert_eq!(info.color_type, ::png::ColorType::Rgb);
+        }
+    }
+}
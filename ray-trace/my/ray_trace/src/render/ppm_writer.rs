@@ -0,0 +1,126 @@
+use crate::common::vec3::Radiance;
+use crate::render::output_format::{OutputFormat, RowWriter};
+use crate::render::quantize::ColorEncoding;
+use std::io::{BufWriter, Write};
+
+// the netpbm family: P3, P6 and P5
+pub(crate) struct PpmWriter {
+    file: BufWriter<Box<dyn Write>>,
+    format: OutputFormat,
+    encoding: ColorEncoding,
+}
+
+impl PpmWriter {
+    pub fn new(
+        mut file: BufWriter<Box<dyn Write>>,
+        format: OutputFormat,
+        width: usize,
+        height: usize,
+        encoding: ColorEncoding,
+    ) -> std::io::Result<Self> {
+        let magic = match format {
+            OutputFormat::PpmAscii => "P3",
+            OutputFormat::PpmBinary => "P6",
+            OutputFormat::PgmBinary => "P5",
+            _ => unreachable!("{:?} is not a netpbm format", format),
+        };
+        write!(file, "{}\n{} {}\n255\n", magic, width, height)?;
+
+        Ok(Self {
+            file,
+            format,
+            encoding,
+        })
+    }
+
+    fn write_pixels(&mut self, pixels: &[(u8, u8, u8)]) -> std::io::Result<()> {
+        match self.format {
+            OutputFormat::PpmAscii => {
+                for pixel in pixels {
+                    writeln!(self.file, "{} {} {}", pixel.0, pixel.1, pixel.2)?;
+                }
+                Ok(())
+            }
+            OutputFormat::PgmBinary => {
+                let bytes: Vec<u8> = pixels.iter().map(luma).collect();
+                self.file.write_all(&bytes)
+            }
+            _ => {
+                let bytes: Vec<u8> = pixels
+                    .iter()
+                    .flat_map(|pixel| [pixel.0, pixel.1, pixel.2])
+                    .collect();
+                self.file.write_all(&bytes)
+            }
+        }
+    }
+}
+
+impl RowWriter for PpmWriter {
+    fn write_row(&mut self, row: usize, pixels: &[Radiance]) -> std::io::Result<()> {
+        let pixels: Vec<_> = pixels
+            .iter()
+            .enumerate()
+            .map(|(col, radiance)| self.encoding.encode_u8(radiance, row, col))
+            .collect();
+        self.write_pixels(&pixels)?;
+        // flush every row, so a half rendered image can be looked at
+        self.file.flush()
+    }
+
+    fn finish(mut self: Box<Self>) -> std::io::Result<()> {
+        self.file.flush()
+    }
+}
+
+// Rec. 709 weights, a grey pixel keeps its value
+fn luma(pixel: &(u8, u8, u8)) -> u8 {
+    (0.2126 * pixel.0 as f64 + 0.7152 * pixel.1 as f64 + 0.0722 * pixel.2 as f64).round() as u8
+}
+
+////////// UT //////////
+#[test]
+fn test_write() {
+    use crate::render::quantize::{Dither, TransferFunction};
+    use crate::render::tone_mapping::ToneMapping;
+
+    let encoding = ColorEncoding {
+        exposure: 0.0,
+        tone_mapping: ToneMapping::Clamp,
+        transfer: TransferFunction::Linear,
+        dither: Dither::None,
+    };
+    let write = |format: OutputFormat| {
+        let file = std::env::temp_dir().join(format!(
+            "ray_trace_test_{}_{:?}.ppm",
+            std::process::id(),
+            format
+        ));
+        let out: Box<dyn Write> = Box::new(std::fs::File::create(&file).unwrap());
+        let mut writer =
+            Box::new(PpmWriter::new(BufWriter::new(out), format, 2, 1, encoding).unwrap());
+        writer
+            .write_row(
+                0,
+                &[
+                    Radiance::new(1.0, 0.0, 0.0),
+                    Radiance::new(7.0, 7.0, 7.0) / 255.0,
+                ],
+            )
+            .unwrap();
+        writer.finish().unwrap();
+        let bytes = std::fs::read(&file).unwrap();
+        std::fs::remove_file(file).unwrap();
+        bytes
+    };
+
+    assert_eq!(
+        write(OutputFormat::PpmAscii),
+        b"P3\n2 1\n255\n255 0 0\n7 7 7\n"
+    );
+    assert_eq!(
+        write(OutputFormat::PpmBinary),
+        b"P6\n2 1\n255\n\xff\x00\x00\x07\x07\x07"
+    );
+    assert_eq!(write(OutputFormat::PgmBinary), b"P5\n2 1\n255\n\x36\x07");
+}
@@ -0,0 +1,285 @@
+use crate::common::vec3::{Radiance, Vec3};
+use crate::render::tone_mapping::{self, ToneMapping};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::sync::OnceLock;
+
+// encodes tone mapped linear values (0~1) for display
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TransferFunction {
+    Linear,
+    // v^(1/gamma), 2.0 is the old `sqrt`
+    Gamma(f64),
+    // the piecewise sRGB OETF
+    Srgb,
+}
+
+impl Default for TransferFunction {
+    fn default() -> Self {
+        Self::Gamma(2.0)
+    }
+}
+
+impl TransferFunction {
+    pub fn encode(&self, linear: &Vec3) -> Vec3 {
+        Vec3::new(
+            self.encode_channel(linear.x),
+            self.encode_channel(linear.y),
+            self.encode_channel(linear.z),
+        )
+    }
+
+    fn encode_channel(&self, v: f64) -> f64 {
+        let v = v.clamp(0.0, 1.0);
+        match *self {
+            Self::Linear => v,
+            Self::Gamma(gamma) => v.powf(1.0 / gamma),
+            Self::Srgb => {
+                if v <= 0.003_130_8 {
+                    12.92 * v
+                } else {
+                    1.055 * v.powf(1.0 / 2.4) - 0.055
+                }
+            }
+        }
+    }
+}
+
+// threshold pattern added before cutting to 8 bits, hides banding in
+// smooth gradients
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Dither {
+    // round to nearest
+    #[default]
+    None,
+    // 8x8 Bayer matrix
+    Ordered,
+    // 64x64 void-and-cluster mask, no visible pattern
+    BlueNoise,
+}
+
+impl Dither {
+    // threshold in 0~1 for the pixel, 0.5 means plain rounding
+    fn threshold(&self, row: usize, col: usize) -> f64 {
+        match *self {
+            Self::None => 0.5,
+            Self::Ordered => (bayer(row % 8, col % 8) as f64 + 0.5) / 64.0,
+            Self::BlueNoise => {
+                let rank =
+                    blue_noise()[(row % BLUE_NOISE_SIZE) * BLUE_NOISE_SIZE + col % BLUE_NOISE_SIZE];
+                (rank as f64 + 0.5) / (BLUE_NOISE_SIZE * BLUE_NOISE_SIZE) as f64
+            }
+        }
+    }
+}
+
+// everything between the linear radiance of a pixel and the numbers stored
+// in a display referred image
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorEncoding {
+    pub exposure: f64, // in stops
+    pub tone_mapping: ToneMapping,
+    pub transfer: TransferFunction,
+    pub dither: Dither,
+}
+
+impl ColorEncoding {
+    // exposed, tone mapped and transfer encoded, in 0~1
+    pub fn encode(&self, radiance: &Radiance) -> Vec3 {
+        let exposed = tone_mapping::expose(radiance.clone(), self.exposure);
+        self.transfer.encode(&self.tone_mapping.map(&exposed))
+    }
+
+    pub fn encode_u8(&self, radiance: &Radiance, row: usize, col: usize) -> (u8, u8, u8) {
+        quantize(&self.encode(radiance), self.dither, row, col)
+    }
+
+    // 16 bits leave no banding to hide, so no dithering
+    pub fn encode_u16(&self, radiance: &Radiance) -> (u16, u16, u16) {
+        let encoded = self.encode(radiance);
+        let channel = |v: f64| (v * 65535.0).round().clamp(0.0, 65535.0) as u16;
+        (channel(encoded.x), channel(encoded.y), channel(encoded.z))
+    }
+}
+
+// encoded 0~1 color to 8 bits per channel
+pub fn quantize(encoded: &Vec3, dither: Dither, row: usize, col: usize) -> (u8, u8, u8) {
+    let threshold = dither.threshold(row, col);
+    let channel = |v: f64| (v * 255.0 + threshold).floor().clamp(0.0, 255.0) as u8;
+    (channel(encoded.x), channel(encoded.y), channel(encoded.z))
+}
+
+// Bayer matrix entry by bit reversing the interleaved bits of (row ^ col, row)
+fn bayer(row: usize, col: usize) -> usize {
+    let xor = row ^ col;
+    let mut value = 0;
+    for bit in 0..3 {
+        value |= ((xor >> bit) & 1) << (5 - 2 * bit);
+        value |= ((row >> bit) & 1) << (4 - 2 * bit);
+    }
+    value
+}
+
+const BLUE_NOISE_SIZE: usize = 64;
+
+fn blue_noise() -> &'static [usize] {
+    static MASK: OnceLock<Vec<usize>> = OnceLock::new();
+    MASK.get_or_init(|| void_and_cluster(BLUE_NOISE_SIZE, 1.5))
+}
+
+// Ulichney's void-and-cluster, returns the rank of every pixel, row-major
+fn void_and_cluster(size: usize, sigma: f64) -> Vec<usize> {
+    let count = size * size;
+
+    // gaussian falloff by toroidal offset, so the mask tiles seamlessly
+    let mut kernel = vec![0.0; count];
+    for dy in 0..size {
+        for dx in 0..size {
+            let y = dy.min(size - dy) as f64;
+            let x = dx.min(size - dx) as f64;
+            kernel[dy * size + dx] = (-(x * x + y * y) / (2.0 * sigma * sigma)).exp();
+        }
+    }
+    let update = |energy: &mut [f64], index: usize, sign: f64| {
+        let (py, px) = (index / size, index % size);
+        for y in 0..size {
+            for x in 0..size {
+                let dy = (y + size - py) % size;
+                let dx = (x + size - px) % size;
+                energy[y * size + x] += sign * kernel[dy * size + dx];
+            }
+        }
+    };
+    // tightest cluster is the set pixel with most energy, largest void the
+    // unset one with the least
+    let tightest_cluster = |pattern: &[bool], energy: &[f64]| {
+        (0..count)
+            .filter(|&i| pattern[i])
+            .max_by(|&a, &b| energy[a].partial_cmp(&energy[b]).unwrap())
+            .unwrap()
+    };
+    let largest_void = |pattern: &[bool], energy: &[f64]| {
+        (0..count)
+            .filter(|&i| !pattern[i])
+            .min_by(|&a, &b| energy[a].partial_cmp(&energy[b]).unwrap())
+            .unwrap()
+    };
+
+    // random initial pattern, then even it out by moving cluster pixels into voids
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut pattern = vec![false; count];
+    let mut energy = vec![0.0; count];
+    let ones = count / 10;
+    while pattern.iter().filter(|&&set| set).count() < ones {
+        let index = rng.gen_range(0, count);
+        if !pattern[index] {
+            pattern[index] = true;
+            update(&mut energy, index, 1.0);
+        }
+    }
+    loop {
+        let cluster = tightest_cluster(&pattern, &energy);
+        pattern[cluster] = false;
+        update(&mut energy, cluster, -1.0);
+        let void = largest_void(&pattern, &energy);
+        pattern[void] = true;
+        update(&mut energy, void, 1.0);
+        if void == cluster {
+            break;
+        }
+    }
+
+    let mut rank = vec![0; count];
+
+    // ranks below `ones`, remove the tightest cluster one at a time
+    let mut removing = pattern.clone();
+    let mut removing_energy = energy.clone();
+    for r in (0..ones).rev() {
+        let cluster = tightest_cluster(&removing, &removing_energy);
+        removing[cluster] = false;
+        update(&mut removing_energy, cluster, -1.0);
+        rank[cluster] = r;
+    }
+
+    // ranks from `ones` up, fill the largest void one at a time
+    for r in ones..count {
+        let void = largest_void(&pattern, &energy);
+        pattern[void] = true;
+        update(&mut energy, void, 1.0);
+        rank[void] = r;
+    }
+
+    rank
+}
+
+////////// UT //////////
+#[test]
+fn test_srgb() {
+    let srgb = TransferFunction::Srgb;
+    assert_eq!(srgb.encode_channel(0.0), 0.0);
+    assert!((srgb.encode_channel(1.0) - 1.0).abs() < 1e-12);
+    // the two pieces meet
+    let knee = 0.003_130_8;
+    assert!((srgb.encode_channel(knee) - srgb.encode_channel(knee + 1e-12)).abs() < 1e-6);
+    assert!((srgb.encode_channel(0.5) - 0.735_356_9).abs() < 1e-6);
+}
+
+#[test]
+fn test_gamma() {
+    assert_eq!(TransferFunction::Gamma(2.0).encode_channel(0.25), 0.5);
+    assert_eq!(TransferFunction::Linear.encode_channel(0.25), 0.25);
+}
+
+#[test]
+fn test_quantize_rounds() {
+    let encoded = Vec3::new(0.5, 1.0 / 255.0 * 0.49, 1.0 / 255.0 * 0.51);
+    assert_eq!(quantize(&encoded, Dither::None, 0, 0), (128, 0, 1));
+}
+
+#[test]
+fn test_encoding() {
+    let encoding = ColorEncoding {
+        exposure: 1.0,
+        tone_mapping: ToneMapping::Clamp,
+        transfer: TransferFunction::Linear,
+        dither: Dither::None,
+    };
+    let radiance = Radiance::new(0.25, 0.5, 1.0);
+    assert_eq!(encoding.encode_u8(&radiance, 0, 0), (128, 255, 255));
+    assert_eq!(encoding.encode_u16(&radiance), (32768, 65535, 65535));
+}
+
+#[test]
+fn test_bayer() {
+    assert_eq!(
+        (0..8).map(|col| bayer(0, col)).collect::<Vec<_>>(),
+        vec![0, 32, 8, 40, 2, 34, 10, 42]
+    );
+    let mut all: Vec<_> = (0..64).map(|i| bayer(i / 8, i % 8)).collect();
+    all.sort_unstable();
+    assert_eq!(all, (0..64).collect::<Vec<_>>());
+}
+
+#[test]
+fn test_dither_keeps_average() {
+    for dither in [Dither::Ordered, Dither::BlueNoise] {
+        let level = 100.3 / 255.0;
+        let encoded = Vec3::new(level, level, level);
+        let (rows, cols) = (64, 64);
+        let sum: usize = (0..rows * cols)
+            .map(|i| quantize(&encoded, dither, i / cols, i % cols).0 as usize)
+            .sum();
+        let average = sum as f64 / (rows * cols) as f64;
+        assert!((average - 100.3).abs() < 0.02, "{:?}: {}", dither, average);
+    }
+}
+
+#[test]
+fn test_blue_noise_is_a_permutation() {
+    let mut ranks = blue_noise().to_vec();
+    ranks.sort_unstable();
+    assert_eq!(
+        ranks,
+        (0..BLUE_NOISE_SIZE * BLUE_NOISE_SIZE).collect::<Vec<_>>()
+    );
+}
@@ -0,0 +1,312 @@
+// where in a pixel (and later on the lens, in the shutter interval, over a
+// bsdf) the samples go. A sampler is stateless: the same pixel, sample index
+// and dimension always give the same point, so threads don't need to share
+// anything. `count` is how many samples the pixel takes in total, `dimension`
+// picks an independent pattern, 0 is the position in the pixel
+pub trait Sampler {
+    // a point in [0, 1)^2
+    fn get_2d(&self, pixel: u64, index: usize, count: usize, dimension: u32) -> (f64, f64);
+}
+
+////////// patterns //////////
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum SamplePattern {
+    // uniform random points, clumps and gaps included
+    #[default]
+    Independent,
+    // one random point in every cell of a sqrt(count) x sqrt(count) grid
+    Stratified,
+    // radical inverse in base 2 and 3, randomly shifted per pixel
+    Halton,
+    // Owen scrambled Sobol (0, 2)-sequence
+    Sobol,
+    // Kensler's correlated multi-jittered, stratified in 2D and in each 1D projection
+    CorrelatedMultiJittered,
+}
+
+impl Sampler for SamplePattern {
+    fn get_2d(&self, pixel: u64, index: usize, count: usize, dimension: u32) -> (f64, f64) {
+        match self {
+            Self::Independent => IndependentSampler.get_2d(pixel, index, count, dimension),
+            Self::Stratified => StratifiedSampler.get_2d(pixel, index, count, dimension),
+            Self::Halton => HaltonSampler.get_2d(pixel, index, count, dimension),
+            Self::Sobol => SobolSampler.get_2d(pixel, index, count, dimension),
+            Self::CorrelatedMultiJittered => {
+                CorrelatedMultiJitteredSampler.get_2d(pixel, index, count, dimension)
+            }
+        }
+    }
+}
+
+pub struct IndependentSampler;
+
+impl Sampler for IndependentSampler {
+    fn get_2d(&self, pixel: u64, index: usize, _count: usize, dimension: u32) -> (f64, f64) {
+        let h = hash(&[pixel, index as u64, dimension as u64]);
+        (to_unit(h), to_unit(mix(h)))
+    }
+}
+
+pub struct StratifiedSampler;
+
+impl Sampler for StratifiedSampler {
+    fn get_2d(&self, pixel: u64, index: usize, count: usize, dimension: u32) -> (f64, f64) {
+        let side = (count.max(1) as f64).sqrt().ceil() as usize;
+        let cells = side * side;
+        // samples past `count` start another round over the grid
+        let round = (index / cells) as u64;
+        let seed = hash(&[pixel, dimension as u64, round]);
+        // shuffle the cells, so different dimensions don't line up
+        let cell = permute((index % cells) as u32, cells as u32, seed as u32) as usize;
+        let jitter = hash(&[seed, index as u64]);
+        (
+            ((cell % side) as f64 + to_unit(jitter)) / side as f64,
+            ((cell / side) as f64 + to_unit(mix(jitter))) / side as f64,
+        )
+    }
+}
+
+pub struct HaltonSampler;
+
+const PRIMES: [u64; 16] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53];
+
+impl Sampler for HaltonSampler {
+    fn get_2d(&self, pixel: u64, index: usize, _count: usize, dimension: u32) -> (f64, f64) {
+        // higher dimensions of halton correlate badly, reuse the first bases
+        let bases = (dimension as usize * 2) % PRIMES.len();
+        let shift = hash(&[pixel, dimension as u64]);
+        // index 0 is the origin in every base, skip it
+        let index = index as u64 + 1;
+        (
+            (radical_inverse(PRIMES[bases], index) + to_unit(shift)).fract(),
+            (radical_inverse(PRIMES[bases + 1], index) + to_unit(mix(shift))).fract(),
+        )
+    }
+}
+
+pub struct SobolSampler;
+
+impl Sampler for SobolSampler {
+    // Burley's hash based Owen scrambling, every dimension shuffles the index
+    // and scrambles the same two sobol dimensions with its own seed
+    fn get_2d(&self, pixel: u64, index: usize, _count: usize, dimension: u32) -> (f64, f64) {
+        let seed = hash(&[pixel, dimension as u64]);
+        let index = nested_uniform_scramble(index as u32, seed as u32);
+        let x = nested_uniform_scramble(index.reverse_bits(), mix(seed) as u32);
+        let y = nested_uniform_scramble(sobol_second(index), (mix(seed) >> 32) as u32);
+        (to_unit_u32(x), to_unit_u32(y))
+    }
+}
+
+pub struct CorrelatedMultiJitteredSampler;
+
+impl Sampler for CorrelatedMultiJitteredSampler {
+    fn get_2d(&self, pixel: u64, index: usize, count: usize, dimension: u32) -> (f64, f64) {
+        let count = count.max(1) as u32;
+        let round = index as u64 / count as u64;
+        let p = hash(&[pixel, dimension as u64, round]) as u32;
+        let s = (index as u64 % count as u64) as u32;
+
+        let m = (count as f64).sqrt() as u32;
+        let n = count.div_ceil(m);
+        let s = permute(s, count, p.wrapping_mul(0x5163_3e2d));
+        let sx = permute(s % m, m, p.wrapping_mul(0x68bc_21eb));
+        let sy = permute(s / m, n, p.wrapping_mul(0x02e5_be93));
+        let jx = rand_float(s, p.wrapping_mul(0x967a_889b));
+        let jy = rand_float(s, p.wrapping_mul(0x368c_c8b7));
+        (
+            (sx as f64 + (sy as f64 + jx) / n as f64) / m as f64,
+            ((s / m) as f64 + (sx as f64 + jy) / m as f64) / n as f64,
+        )
+    }
+}
+
+////////// helpers //////////
+// splitmix64 finalizer
+fn mix(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+pub(crate) fn hash(values: &[u64]) -> u64 {
+    values
+        .iter()
+        .fold(0x2545_f491_4f6c_dd1d, |h, &value| mix(h ^ value))
+}
+
+// top 53 bits, in [0, 1)
+fn to_unit(x: u64) -> f64 {
+    (x >> 11) as f64 / (1u64 << 53) as f64
+}
+
+fn to_unit_u32(x: u32) -> f64 {
+    x as f64 / (1u64 << 32) as f64
+}
+
+fn radical_inverse(base: u64, mut index: u64) -> f64 {
+    let inv_base = 1.0 / base as f64;
+    let mut inv = inv_base;
+    let mut result = 0.0;
+    while index > 0 {
+        result += (index % base) as f64 * inv;
+        index /= base;
+        inv *= inv_base;
+    }
+    result
+}
+
+// the second sobol dimension, its generator matrix is the Pascal triangle
+fn sobol_second(mut index: u32) -> u32 {
+    let mut result = 0;
+    let mut v = 1 << 31;
+    while index != 0 {
+        if index & 1 == 1 {
+            result ^= v;
+        }
+        index >>= 1;
+        v ^= v >> 1;
+    }
+    result
+}
+
+// Laine and Karras, an Owen scramble of the bits in reversed order
+fn laine_karras(mut x: u32, seed: u32) -> u32 {
+    x = x.wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50_b47c);
+    x ^= x.wrapping_mul(0xb82f_1e52);
+    x ^= x.wrapping_mul(0xc7af_e638);
+    x ^= x.wrapping_mul(0x8d22_f6e6);
+    x
+}
+
+fn nested_uniform_scramble(x: u32, seed: u32) -> u32 {
+    laine_karras(x.reverse_bits(), seed).reverse_bits()
+}
+
+// a pseudo random permutation of 0..l, picked by `p`
+fn permute(mut i: u32, l: u32, p: u32) -> u32 {
+    let mut w = l.max(1) - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+    loop {
+        i ^= p;
+        i = i.wrapping_mul(0xe170_893d);
+        i ^= p >> 16;
+        i ^= (i & w) >> 4;
+        i ^= p >> 8;
+        i = i.wrapping_mul(0x0929_eb3f);
+        i ^= p >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | p >> 27);
+        i = i.wrapping_mul(0x6935_fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dc_b303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e50_1cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860_a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < l.max(1) {
+            break;
+        }
+    }
+    (i.wrapping_add(p)) % l.max(1)
+}
+
+fn rand_float(mut i: u32, p: u32) -> f64 {
+    i ^= p;
+    i ^= i >> 17;
+    i ^= i >> 10;
+    i = i.wrapping_mul(0xb365_34e5);
+    i ^= i >> 12;
+    i ^= i >> 21;
+    i = i.wrapping_mul(0x93fc_4795);
+    i ^= 0xdf6e_307f;
+    i ^= i >> 17;
+    i = i.wrapping_mul(1 | p >> 18);
+    to_unit_u32(i)
+}
+
+////////// UT //////////
+#[cfg(test)]
+const ALL_PATTERNS: [SamplePattern; 5] = [
+    SamplePattern::Independent,
+    SamplePattern::Stratified,
+    SamplePattern::Halton,
+    SamplePattern::Sobol,
+    SamplePattern::CorrelatedMultiJittered,
+];
+
+#[test]
+fn test_in_unit_square() {
+    for pattern in ALL_PATTERNS {
+        for pixel in 0..8 {
+            for index in 0..40 {
+                let (x, y) = pattern.get_2d(pixel, index, 32, index as u32 % 3);
+                assert!((0.0..1.0).contains(&x), "{:?} {}", pattern, x);
+                assert!((0.0..1.0).contains(&y), "{:?} {}", pattern, y);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_deterministic() {
+    for pattern in ALL_PATTERNS {
+        assert_eq!(pattern.get_2d(7, 3, 16, 1), pattern.get_2d(7, 3, 16, 1));
+        assert_ne!(pattern.get_2d(7, 3, 16, 1), pattern.get_2d(8, 3, 16, 1));
+    }
+}
+
+#[test]
+fn test_stratified_patterns() {
+    // 16 samples fill every cell of a 4x4 grid once, and Sobol and CMJ also
+    // every 1/16 wide column and row
+    let cell = |v: f64, n: usize| (v * n as f64) as usize;
+    for pattern in [
+        SamplePattern::Stratified,
+        SamplePattern::Sobol,
+        SamplePattern::CorrelatedMultiJittered,
+    ] {
+        for pixel in 0..16 {
+            let points: Vec<_> = (0..16).map(|i| pattern.get_2d(pixel, i, 16, 0)).collect();
+            let mut grid: Vec<_> = points
+                .iter()
+                .map(|&(x, y)| cell(y, 4) * 4 + cell(x, 4))
+                .collect();
+            grid.sort_unstable();
+            assert_eq!(grid, (0..16).collect::<Vec<_>>(), "{:?}", pattern);
+            if pattern != SamplePattern::Stratified {
+                let mut xs: Vec<_> = points.iter().map(|&(x, _)| cell(x, 16)).collect();
+                xs.sort_unstable();
+                assert_eq!(xs, (0..16).collect::<Vec<_>>(), "{:?}", pattern);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_radical_inverse() {
+    assert_eq!(radical_inverse(2, 1), 0.5);
+    assert_eq!(radical_inverse(2, 3), 0.75);
+    assert!((radical_inverse(3, 1) - 1.0 / 3.0).abs() < 1e-12);
+    assert_eq!(
+        (0..4).map(sobol_second).collect::<Vec<_>>(),
+        vec![0, 1 << 31, 3 << 30, 1 << 30]
+    );
+}
+
+#[test]
+fn test_permute() {
+    for l in [1, 5, 16, 100] {
+        let mut values: Vec<_> = (0..l).map(|i| permute(i, l, 12345)).collect();
+        values.sort_unstable();
+        assert_eq!(values, (0..l).collect::<Vec<_>>());
+    }
+}
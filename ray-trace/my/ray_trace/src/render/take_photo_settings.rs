@@ -1,11 +1,18 @@
 use crate::common::color::Color;
-use crate::common::vec3::{Point3, Vec3};
+use crate::common::vec3::Radiance;
 use crate::geometry::world::World;
 use crate::geometry::Geometry;
+use crate::render::adaptive::{self, AdaptiveSampling};
+use crate::render::image::Image;
+use crate::render::image_sink::{FileSink, ImageSink, VecSink};
+use crate::render::output_format::OutputFormat;
 use crate::render::painter::Painter;
+use crate::render::quantize::{ColorEncoding, Dither, TransferFunction};
+use crate::render::sampler::SamplePattern;
+use crate::render::tile::TileOrder;
+use crate::render::tone_mapping::ToneMapping;
 use crate::{common::ray::Ray, render::camera::Camera};
-use std::f64::INFINITY;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 #[derive(Debug)]
 pub struct TakePhotoSettings<'c> {
@@ -13,8 +20,20 @@ pub struct TakePhotoSettings<'c> {
     world: World,
     max_reflection: usize, // depth
     picture_height: usize,
-    gamma: bool,
+    transfer: TransferFunction,
+    dither: Dither,
+    exposure: f64,
+    tone_mapping: ToneMapping,
     samples: usize, // 每个pixel的采样
+    adaptive: Option<AdaptiveSampling>,
+    heatmap: Option<PathBuf>,
+    sampler: SamplePattern,
+    seed: u64,
+    threads: usize,
+    parallel: bool,
+    tile_size: usize,
+    tile_order: TileOrder,
+    format: Option<OutputFormat>, // `None` to pick by file extension
 }
 
 impl<'c> TakePhotoSettings<'c> {
@@ -24,8 +43,20 @@ impl<'c> TakePhotoSettings<'c> {
             world,
             max_reflection: 8,
             picture_height: 108,
-            gamma: true,
+            transfer: TransferFunction::Gamma(2.0),
+            dither: Dither::None,
+            exposure: 0.0,
+            tone_mapping: ToneMapping::Clamp,
             samples: 50,
+            adaptive: None,
+            heatmap: None,
+            sampler: SamplePattern::Independent,
+            seed: 0,
+            threads: 0,
+            parallel: true,
+            tile_size: 0,
+            tile_order: TileOrder::Scanline,
+            format: None,
         }
     }
 
@@ -46,8 +77,34 @@ impl<'c> TakePhotoSettings<'c> {
         self
     }
 
+    // true is the same as `transfer(TransferFunction::Gamma(2.0))`
     pub const fn gamma(mut self, gamma: bool) -> Self {
-        self.gamma = gamma;
+        self.transfer = if gamma {
+            TransferFunction::Gamma(2.0)
+        } else {
+            TransferFunction::Linear
+        };
+        self
+    }
+
+    pub const fn transfer(mut self, transfer: TransferFunction) -> Self {
+        self.transfer = transfer;
+        self
+    }
+
+    pub const fn dither(mut self, dither: Dither) -> Self {
+        self.dither = dither;
+        self
+    }
+
+    // in stops, applied before tone mapping
+    pub const fn exposure(mut self, exposure: f64) -> Self {
+        self.exposure = exposure;
+        self
+    }
+
+    pub const fn tone_mapping(mut self, tone_mapping: ToneMapping) -> Self {
+        self.tone_mapping = tone_mapping;
         self
     }
 
@@ -56,28 +113,72 @@ impl<'c> TakePhotoSettings<'c> {
         self
     }
 
+    // replaces the fixed `samples` count
+    pub const fn adaptive(mut self, adaptive: AdaptiveSampling) -> Self {
+        self.adaptive = Some(adaptive);
+        self
+    }
+
+    // `shot` also saves how many samples every pixel took
+    pub fn heatmap<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.heatmap = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    // where in the pixel the samples go
+    pub const fn sampler(mut self, sampler: SamplePattern) -> Self {
+        self.sampler = sampler;
+        self
+    }
+
+    // every random draw derives from it, same seed gives the same picture
+    pub const fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    // 0 means one thread per cpu
+    pub const fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    pub const fn parallel(mut self, parallel: bool) -> Self {
+        self.parallel = parallel;
+        self
+    }
+
+    // render `size` x `size` tiles in `order` instead of whole rows, 0 for rows
+    pub const fn tiles(mut self, size: usize, order: TileOrder) -> Self {
+        self.tile_size = size;
+        self.tile_order = order;
+        self
+    }
+
+    pub const fn format(mut self, format: OutputFormat) -> Self {
+        self.format = Some(format);
+        self
+    }
+
     // TODO not pub,
-    fn ray_color(ray: &Ray, world: &World, remain_reflection: usize) -> Color {
+    fn ray_color(ray: &Ray, world: &World, remain_reflection: usize) -> Radiance {
         if remain_reflection == 0 {
-            return Color::default();
+            return Radiance::default();
         }
         if let Some(hit) = world.hit(
             ray,
-            0.001..INFINITY, // not 0.000...INFINITY, $8.23 Fixing Shadow Acne
+            0.001..f64::INFINITY, // not 0.000...INFINITY, $8.23 Fixing Shadow Acne
         ) {
             let material = hit.material;
             let emitted = material
                 .emitted(hit.u, hit.v, &hit.point)
-                .unwrap_or_default()
-                .into_color(1, false);
+                .unwrap_or_default();
 
-            // scatter成新的光线
+            // scatter成新的光线, filtered by the surface color
             if let Some(scattered) = material.scatter(ray, hit) {
-                // return emitted.gradient(
-                //     // hongfendong scatter                //     0.5,
-                //     Self::ray_color(&scattered.ray, world, remain_reflection - 1),
-                // );
-                return 0.5 * Self::ray_color(&scattered.ray, world, remain_reflection - 1);
+                let attenuation: Radiance = scattered.color.into();
+                return emitted
+                    + attenuation * Self::ray_color(&scattered.ray, world, remain_reflection - 1);
             }
             return emitted;
         }
@@ -85,7 +186,7 @@ impl<'c> TakePhotoSettings<'c> {
         world.background(ray).into()
     }
 
-    pub fn shot<P: AsRef<Path>>(&self, path: Option<P>) -> std::io::Result<()> {
+    fn painter(&self) -> Painter {
         // TODO what is this?
         // #[allow(
         //     clippy::cast_sign_loss,
@@ -93,16 +194,371 @@ impl<'c> TakePhotoSettings<'c> {
         //     clippy::cast_possible_truncation
         // )]
 
-        Painter::new(
+        let painter = Painter::new(
             (self.picture_height as f64 * self.camera.aspect_ratio).round() as usize,
             self.picture_height,
         )
-        // gama.sapmles/thread.parallel
-        .gamma(self.gamma)
+        .transfer(self.transfer)
+        .dither(self.dither)
+        .exposure(self.exposure)
+        .tone_mapping(self.tone_mapping)
         .samples(self.samples)
-        .draw(&path, |u, v| -> Color {
-            let ray = self.camera.ray(u, v);
+        .sampler(self.sampler)
+        .seed(self.seed)
+        .threads(self.threads)
+        .parallel(self.parallel)
+        .tiles(self.tile_size, self.tile_order);
+        match self.adaptive {
+            Some(adaptive) => painter.adaptive(adaptive),
+            None => painter,
+        }
+    }
+
+    // the whole picture in memory, still linear
+    pub fn render(&self) -> std::io::Result<Image> {
+        let mut sink = VecSink::default();
+        self.shot_to(&mut sink)?;
+        Ok(sink.into_image())
+    }
+
+    // to an image file, see `FileSink`. The file is created before rendering
+    // and rows are written as they are done
+    pub fn shot<P: AsRef<Path>>(&self, path: Option<P>) -> std::io::Result<()> {
+        let sink = FileSink::new(path);
+        let mut sink = match self.format {
+            Some(format) => sink.format(format),
+            None => sink,
+        };
+        match &self.heatmap {
+            Some(path) => self.shot_to(&mut HeatmapSink::new(&mut sink, Some(path))),
+            None => self.shot_to(&mut sink),
+        }
+    }
+
+    // rows go to `sink` as they are done, nothing is kept in memory
+    pub fn shot_to<S: ImageSink + ?Sized>(&self, sink: &mut S) -> std::io::Result<()> {
+        self.painter().draw(sink, |sample| -> Radiance {
+            let ray = self.camera.ray(sample);
             Self::ray_color(&ray, &self.world, self.max_reflection) //hongfendong
         })
     }
 }
+
+////////// heatmap //////////
+// passes the picture through and keeps the sample counts, the heatmap is
+// written once all of them are known
+struct HeatmapSink<'s, S: ImageSink + ?Sized> {
+    picture: &'s mut S,
+    heatmap: FileSink,
+    width: usize,
+    samples: Vec<usize>,
+}
+
+impl<'s, S: ImageSink + ?Sized> HeatmapSink<'s, S> {
+    fn new<P: AsRef<Path>>(picture: &'s mut S, path: Option<P>) -> Self {
+        Self {
+            picture,
+            heatmap: FileSink::new(path),
+            width: 0,
+            samples: Vec::new(),
+        }
+    }
+}
+
+impl<'s, S: ImageSink + ?Sized> ImageSink for HeatmapSink<'s, S> {
+    fn begin(
+        &mut self,
+        width: usize,
+        height: usize,
+        encoding: &ColorEncoding,
+    ) -> std::io::Result<()> {
+        self.picture.begin(width, height, encoding)?;
+        let encoding = ColorEncoding {
+            exposure: 0.0,
+            tone_mapping: ToneMapping::Clamp,
+            transfer: TransferFunction::Srgb,
+            dither: Dither::None,
+        };
+        self.heatmap.begin(width, height, &encoding)?;
+        self.width = width;
+        self.samples = Vec::with_capacity(width * height);
+        Ok(())
+    }
+
+    fn samples(&mut self, row: usize, samples: &[usize]) -> std::io::Result<()> {
+        self.samples.extend_from_slice(samples);
+        self.picture.samples(row, samples)
+    }
+
+    fn row(&mut self, row: usize, pixels: &[Radiance]) -> std::io::Result<()> {
+        self.picture.row(row, pixels)
+    }
+
+    fn finish(&mut self) -> std::io::Result<()> {
+        self.picture.finish()?;
+        let max = self.samples.iter().copied().max().unwrap_or_default();
+        for (row, samples) in self.samples.chunks(self.width.max(1)).enumerate() {
+            let pixels: Vec<Radiance> = samples
+                .iter()
+                .map(|&samples| adaptive::heat(samples, max))
+                .collect();
+            self.heatmap.row(row, &pixels)?;
+        }
+        self.heatmap.finish()
+    }
+}
+
+////////// UT //////////
+#[test]
+fn test_ray_color_not_clamped() {
+    use crate::common::vec3::{Point3, Vec3};
+    use crate::geometry::list::GeometryList;
+    use crate::geometry::sphere::Sphere;
+    use crate::material::diffuse_light::DiffuseLight;
+
+    let mut list = GeometryList::default();
+    list.add(Sphere::new(
+        Point3::new(0.0, 0.0, -5.0),
+        1.0,
+        DiffuseLight::new(Color::newf(1.0, 0.5, 0.25)).intensity(4.0),
+    ));
+    let world = list.build(0.0..0.0);
+
+    let ray = Ray::new(Point3::default(), Vec3::new(0.0, 0.0, -1.0));
+    let radiance = TakePhotoSettings::ray_color(&ray, &world, 8);
+    assert_eq!(radiance, Radiance::new(4.0, 2.0, 1.0));
+}
+
+#[test]
+fn test_ray_color_attenuated_by_albedo() {
+    use crate::common::vec3::{Point3, Vec3};
+    use crate::geometry::list::GeometryList;
+    use crate::geometry::sphere::Sphere;
+    use crate::material::lambertian::Lambertian;
+
+    // whichever way the bounce goes it only sees the flat sky
+    let mut list = GeometryList::default();
+    list.add(Sphere::new(
+        Point3::new(0.0, 0.0, -5.0),
+        1.0,
+        Lambertian::new(Color::newf(0.8, 0.4, 0.1)),
+    ));
+    let mut world = list.build(0.0..0.0);
+    world.set_bg(|_: &Ray| Color::newf(0.5, 1.0, 0.25));
+
+    let ray = Ray::new(Point3::default(), Vec3::new(0.0, 0.0, -1.0));
+    for _ in 0..16 {
+        let radiance = TakePhotoSettings::ray_color(&ray, &world, 8);
+        let expected = Radiance::new(0.8 * 0.5, 0.4 * 1.0, 0.1 * 0.25);
+        assert!((&radiance - &expected).length() < 1e-12, "{:?}", radiance);
+    }
+}
+
+#[test]
+fn test_render_to_image() {
+    use crate::common::vec3::Point3;
+    use crate::geometry::list::GeometryList;
+    use crate::geometry::sphere::Sphere;
+    use crate::geometry::world::black_background;
+    use crate::material::diffuse_light::DiffuseLight;
+    use crate::render::camera::CameraBuilder;
+
+    // a light right in front of the camera, so big it fills the view
+    let mut list = GeometryList::default();
+    list.add(Sphere::new(
+        Point3::new(0.0, 0.0, -10.0),
+        9.9,
+        DiffuseLight::new(Color::newf(1.0, 0.5, 0.25)).intensity(2.0),
+    ));
+    let camera = CameraBuilder::default().aspect_ratio(2.0).build();
+    let image = camera
+        .take_photo(list)
+        .background(black_background)
+        .height(4)
+        .samples(4)
+        .render()
+        .unwrap();
+
+    assert_eq!((image.width(), image.height()), (8, 4));
+    assert!(image
+        .pixels()
+        .iter()
+        .all(|pixel| *pixel == Radiance::new(2.0, 1.0, 0.5)));
+}
+
+#[test]
+fn test_same_seed_same_picture() {
+    use crate::common::vec3::Point3;
+    use crate::geometry::list::GeometryList;
+    use crate::geometry::sphere::Sphere;
+    use crate::material::dielectric::Dielectric;
+    use crate::material::lambertian::Lambertian;
+    use crate::render::camera::CameraBuilder;
+
+    let camera = CameraBuilder::default().aspect_ratio(2.0).build();
+    let render = |seed: u64, threads: usize, tiles: usize| {
+        let mut list = GeometryList::default();
+        list.add(Sphere::new(
+            Point3::new(0.0, -100.5, -1.0),
+            100.0,
+            Lambertian::new(Color::newf(0.5, 0.5, 0.5)),
+        ))
+        .add(Sphere::new(
+            Point3::new(0.0, 0.0, -1.0),
+            0.5,
+            Dielectric::new(1.5),
+        ));
+        camera
+            .take_photo(list)
+            .height(6)
+            .samples(4)
+            .seed(seed)
+            .threads(threads)
+            .tiles(tiles, TileOrder::Spiral)
+            .render()
+            .unwrap()
+    };
+
+    let image = render(7, 1, 0);
+    assert_eq!(image, render(7, 4, 0));
+    assert_eq!(image, render(7, 3, 4));
+    assert_ne!(image, render(8, 1, 0));
+}
+
+#[test]
+fn test_depth_of_field_with_one_sample() {
+    use crate::common::vec3::Point3;
+    use crate::geometry::list::GeometryList;
+    use crate::geometry::sphere::Sphere;
+    use crate::geometry::world::black_background;
+    use crate::material::diffuse_light::DiffuseLight;
+    use crate::render::camera::CameraBuilder;
+
+    // a light well in front of the focus plane, only a pinhole keeps it sharp
+    let render = |aperture: f64| {
+        let camera = CameraBuilder::default()
+            .aspect_ratio(1.0)
+            .aperture(aperture)
+            .focus_distance(100.0)
+            .build();
+        let mut list = GeometryList::default();
+        list.add(Sphere::new(
+            Point3::new(0.0, 0.0, -3.0),
+            1.0,
+            DiffuseLight::new(Color::newf(1.0, 1.0, 1.0)),
+        ));
+        camera
+            .take_photo(list)
+            .background(black_background)
+            .height(8)
+            .samples(1)
+            .render()
+            .unwrap()
+    };
+
+    assert_ne!(render(0.0), render(2.0));
+}
+
+#[test]
+fn test_motion_blur() {
+    use crate::common::vec3::Point3;
+    use crate::geometry::list::GeometryList;
+    use crate::geometry::moving_sphere::MovingSphere;
+    use crate::geometry::world::black_background;
+    use crate::material::diffuse_light::DiffuseLight;
+    use crate::render::camera::CameraBuilder;
+
+    // a light crossing the whole view while the shutter is open
+    let render = |shutter_speed: f64, samples: usize, (from, to): (f64, f64)| {
+        let camera = CameraBuilder::default()
+            .aspect_ratio(1.0)
+            .shutter_speed(shutter_speed)
+            .build();
+        let mut list = GeometryList::default();
+        list.add(MovingSphere::new(
+            (Point3::new(from, 0.0, -3.0), Point3::new(to, 0.0, -3.0)),
+            0.0..1.0,
+            1.0,
+            DiffuseLight::new(Color::newf(1.0, 1.0, 1.0)),
+        ));
+        camera
+            .take_photo(list)
+            .background(black_background)
+            .height(9)
+            .samples(samples)
+            .render()
+            .unwrap()
+    };
+
+    // frozen at its start, the light is left of the view
+    let still = render(0.0, 64, (-6.0, 6.0));
+    assert!(still.row(4).iter().all(|pixel| pixel.x == 0.0));
+    // over the exposure it streaks through every pixel of the middle row
+    let blurred = render(1.0, 64, (-6.0, 6.0));
+    assert!(blurred
+        .row(4)
+        .iter()
+        .all(|pixel| pixel.x > 0.0 && pixel.x < 1.0));
+
+    // one sample per pixel still catches the light at its own time, stuck in
+    // the middle of the shutter it would look like a light at rest there
+    let single = render(1.0, 1, (-6.0, 6.0));
+    assert_ne!(single, render(1.0, 1, (0.0, 0.0)));
+}
+
+#[test]
+fn test_shot_fails_before_rendering() {
+    use crate::geometry::list::GeometryList;
+    use crate::render::camera::CameraBuilder;
+
+    let camera = CameraBuilder::default().build();
+    let missing = std::env::temp_dir()
+        .join(format!("ray_trace_missing_{}", std::process::id()))
+        .join("picture.ppm");
+    let settings = || {
+        camera
+            .take_photo(GeometryList::default())
+            .background(|_: &Ray| -> Color { panic!("rendered before the file was opened") })
+            .height(2)
+            .samples(1)
+    };
+
+    assert!(settings().shot(Some(&missing)).is_err());
+    let picture = std::env::temp_dir().join(format!("ray_trace_shot_{}.ppm", std::process::id()));
+    assert!(settings().heatmap(&missing).shot(Some(&picture)).is_err());
+    let _ = std::fs::remove_file(picture);
+}
+
+#[test]
+fn test_shot_with_heatmap() {
+    use crate::geometry::list::GeometryList;
+    use crate::geometry::world::black_background;
+    use crate::render::camera::CameraBuilder;
+
+    let dir = std::env::temp_dir();
+    let picture = dir.join(format!("ray_trace_shot_{}_picture.ppm", std::process::id()));
+    let heatmap = dir.join(format!("ray_trace_shot_{}_heatmap.ppm", std::process::id()));
+    let camera = CameraBuilder::default().aspect_ratio(2.0).build();
+    camera
+        .take_photo(GeometryList::default())
+        .background(black_background)
+        .height(2)
+        .samples(3)
+        .format(OutputFormat::PpmBinary)
+        .heatmap(&heatmap)
+        .shot(Some(&picture))
+        .unwrap();
+
+    let picture_bytes = std::fs::read(&picture).unwrap();
+    let heatmap_bytes = std::fs::read(&heatmap).unwrap();
+    let _ = std::fs::remove_file(picture);
+    let _ = std::fs::remove_file(heatmap);
+
+    // the picture is P6 as asked, the heatmap keeps the P3 of `.ppm`.
+    // Every pixel took the most samples, all red
+    assert_eq!(picture_bytes, [&b"P6\n4 2\n255\n"[..], &[0; 24]].concat());
+    assert_eq!(
+        heatmap_bytes,
+        [&b"P3\n4 2\n255\n"[..], &b"255 0 0\n".repeat(8)].concat()
+    );
+}
@@ -0,0 +1,149 @@
+use std::ops::Range;
+
+// the order tiles are handed out to the render threads
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum TileOrder {
+    // left to right, top to bottom
+    #[default]
+    Scanline,
+    // from the center tile outwards, so the interesting part shows up first
+    Spiral,
+    // along a hilbert curve, neighbouring tiles are rendered close in time
+    Hilbert,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tile {
+    pub rows: Range<usize>,
+    pub cols: Range<usize>,
+}
+
+impl Tile {
+    pub fn width(&self) -> usize {
+        self.cols.end - self.cols.start
+    }
+
+    pub fn height(&self) -> usize {
+        self.rows.end - self.rows.start
+    }
+}
+
+// cut a `width` x `height` image into `size` x `size` tiles, the tiles on the
+// right and bottom border may be smaller
+pub fn tiles(width: usize, height: usize, size: usize, order: TileOrder) -> Vec<Tile> {
+    let size = size.max(1);
+    let tile_cols = width.div_ceil(size);
+    let tile_rows = height.div_ceil(size);
+
+    let grid = match order {
+        TileOrder::Scanline => scanline_order(tile_cols, tile_rows),
+        TileOrder::Spiral => spiral_order(tile_cols, tile_rows),
+        TileOrder::Hilbert => hilbert_order(tile_cols, tile_rows),
+    };
+
+    grid.into_iter()
+        .map(|(x, y)| Tile {
+            rows: y * size..((y + 1) * size).min(height),
+            cols: x * size..((x + 1) * size).min(width),
+        })
+        .collect()
+}
+
+fn scanline_order(cols: usize, rows: usize) -> Vec<(usize, usize)> {
+    (0..rows)
+        .flat_map(|y| (0..cols).map(move |x| (x, y)))
+        .collect()
+}
+
+fn spiral_order(cols: usize, rows: usize) -> Vec<(usize, usize)> {
+    let total = cols * rows;
+    let mut result = Vec::with_capacity(total);
+    let (mut x, mut y) = ((cols as isize - 1) / 2, (rows as isize - 1) / 2);
+    // right, down, left, up; every two turns the leg grows by one
+    let directions = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+    let mut leg = 1;
+    let mut direction = 0;
+
+    while result.len() < total {
+        for _ in 0..2 {
+            let (dx, dy) = directions[direction % 4];
+            for _ in 0..leg {
+                if (0..cols as isize).contains(&x) && (0..rows as isize).contains(&y) {
+                    result.push((x as usize, y as usize));
+                }
+                x += dx;
+                y += dy;
+            }
+            direction += 1;
+        }
+        leg += 1;
+    }
+    result
+}
+
+fn hilbert_order(cols: usize, rows: usize) -> Vec<(usize, usize)> {
+    let n = cols.max(rows).max(1).next_power_of_two();
+    (0..n * n)
+        .map(|d| hilbert_d2xy(n, d))
+        .filter(|&(x, y)| x < cols && y < rows)
+        .collect()
+}
+
+// distance along the curve to (x, y), for a `n` x `n` grid, `n` a power of two
+fn hilbert_d2xy(n: usize, d: usize) -> (usize, usize) {
+    let (mut x, mut y) = (0, 0);
+    let mut t = d;
+    let mut s = 1;
+    while s < n {
+        let rx = 1 & (t / 2);
+        let ry = 1 & (t ^ rx);
+        if ry == 0 {
+            if rx == 1 {
+                x = s - 1 - x;
+                y = s - 1 - y;
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        x += s * rx;
+        y += s * ry;
+        t /= 4;
+        s *= 2;
+    }
+    (x, y)
+}
+
+////////// UT //////////
+#[test]
+fn test_tiles_cover_image() {
+    for order in [TileOrder::Scanline, TileOrder::Spiral, TileOrder::Hilbert] {
+        let (width, height) = (100, 70);
+        let mut covered = vec![0; width * height];
+        for tile in tiles(width, height, 32, order) {
+            for row in tile.rows.clone() {
+                for col in tile.cols.clone() {
+                    covered[row * width + col] += 1;
+                }
+            }
+        }
+        assert!(covered.iter().all(|&count| count == 1), "{:?}", order);
+    }
+}
+
+#[test]
+fn test_spiral_starts_from_center() {
+    let order = spiral_order(5, 3);
+    assert_eq!(order.len(), 15);
+    assert_eq!(order[0], (2, 1));
+    assert_eq!(order[1], (3, 1));
+}
+
+#[test]
+fn test_hilbert_neighbours() {
+    let order = hilbert_order(8, 8);
+    assert_eq!(order.len(), 64);
+    for pair in order.windows(2) {
+        let dx = (pair[0].0 as isize - pair[1].0 as isize).abs();
+        let dy = (pair[0].1 as isize - pair[1].1 as isize).abs();
+        assert_eq!(dx + dy, 1);
+    }
+}
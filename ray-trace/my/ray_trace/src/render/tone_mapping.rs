@@ -0,0 +1,136 @@
+use crate::common::vec3::Radiance;
+
+// maps unbounded linear radiance into 0~1 before gamma and quantization
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ToneMapping {
+    // cut everything above 1.0
+    #[default]
+    Clamp,
+    // x / (1 + x)
+    Reinhard,
+    // Reinhard that maps `white` to 1.0 instead of infinity,
+    // see `extended_reinhard`
+    ExtendedReinhard {
+        white: f64,
+    },
+    // Narkowicz's curve fit of the ACES filmic RRT + ODT
+    AcesFilmic,
+    // John Hable's Uncharted 2 filmic curve
+    Hable,
+}
+
+// the curve divides by `white` squared
+const MIN_WHITE: f64 = 1e-6;
+
+impl ToneMapping {
+    // a `white` of 0 or below would map everything to NaN or infinity, it is
+    // raised to a tiny positive value instead
+    pub const fn extended_reinhard(white: f64) -> Self {
+        Self::ExtendedReinhard {
+            white: white.max(MIN_WHITE),
+        }
+    }
+
+    pub fn map(&self, radiance: &Radiance) -> Radiance {
+        Radiance::new(
+            self.map_channel(radiance.x),
+            self.map_channel(radiance.y),
+            self.map_channel(radiance.z),
+        )
+    }
+
+    fn map_channel(&self, x: f64) -> f64 {
+        let x = x.max(0.0);
+        match *self {
+            Self::Clamp => x.min(1.0),
+            Self::Reinhard => x / (1.0 + x),
+            Self::ExtendedReinhard { white } => {
+                // the variant can be built by hand, so check here too
+                let white = white.max(MIN_WHITE);
+                (x * (1.0 + x / (white * white)) / (1.0 + x)).min(1.0)
+            }
+            Self::AcesFilmic => {
+                let (a, b, c, d, e) = (2.51, 0.03, 2.43, 0.59, 0.14);
+                ((x * (a * x + b)) / (x * (c * x + d) + e)).min(1.0)
+            }
+            Self::Hable => {
+                const EXPOSURE_BIAS: f64 = 2.0;
+                const WHITE: f64 = 11.2;
+                (Self::hable_partial(x * EXPOSURE_BIAS) / Self::hable_partial(WHITE)).min(1.0)
+            }
+        }
+    }
+
+    fn hable_partial(x: f64) -> f64 {
+        let (a, b, c, d, e, f) = (0.15, 0.50, 0.10, 0.20, 0.02, 0.30);
+        ((x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f)) - e / f
+    }
+}
+
+// scale radiance by 2^stops, like opening the aperture by `stops`
+pub fn expose(radiance: Radiance, stops: f64) -> Radiance {
+    if stops == 0.0 {
+        radiance
+    } else {
+        radiance * 2f64.powf(stops)
+    }
+}
+
+////////// UT //////////
+#[test]
+fn test_map_range() {
+    for tone_mapping in [
+        ToneMapping::Clamp,
+        ToneMapping::Reinhard,
+        ToneMapping::extended_reinhard(4.0),
+        ToneMapping::AcesFilmic,
+        ToneMapping::Hable,
+    ] {
+        let mut last = -1.0;
+        for i in 0..100 {
+            let x = tone_mapping.map_channel(i as f64 * 0.25);
+            assert!((0.0..=1.0).contains(&x), "{:?}", tone_mapping);
+            assert!(x >= last, "{:?} should not decrease", tone_mapping);
+            last = x;
+        }
+        assert_eq!(tone_mapping.map_channel(0.0), 0.0, "{:?}", tone_mapping);
+    }
+}
+
+#[test]
+fn test_map_values() {
+    assert_eq!(ToneMapping::Clamp.map_channel(3.0), 1.0);
+    assert_eq!(ToneMapping::Reinhard.map_channel(1.0), 0.5);
+    assert_eq!(
+        ToneMapping::ExtendedReinhard { white: 4.0 }.map_channel(4.0),
+        1.0
+    );
+    assert!((ToneMapping::Hable.map_channel(11.2 / 2.0) - 1.0).abs() < 1e-12);
+}
+
+#[test]
+fn test_extended_reinhard_non_positive_white() {
+    for white in [0.0, -4.0, f64::NAN] {
+        assert_eq!(
+            ToneMapping::extended_reinhard(white),
+            ToneMapping::ExtendedReinhard { white: MIN_WHITE }
+        );
+        let tone_mapping = ToneMapping::ExtendedReinhard { white };
+        assert_eq!(tone_mapping.map_channel(0.0), 0.0);
+        for x in [1e-9, 0.5, 1e6] {
+            let y = tone_mapping.map_channel(x);
+            assert!((0.0..=1.0).contains(&y), "{} maps to {}", x, y);
+        }
+    }
+    assert_eq!(
+        ToneMapping::extended_reinhard(4.0),
+        ToneMapping::ExtendedReinhard { white: 4.0 }
+    );
+}
+
+#[test]
+fn test_expose() {
+    let radiance = Radiance::new(0.25, 0.5, 1.0);
+    assert_eq!(expose(radiance.clone(), 2.0), Radiance::new(1.0, 2.0, 4.0));
+    assert_eq!(expose(radiance, -1.0), Radiance::new(0.125, 0.25, 0.5));
+}